@@ -1,73 +1,232 @@
-use rlisp::*;
+use crate::*;
+use crate::evals::apply;
+use crate::numbers::{self, Number};
+use std::cmp::Ordering;
 use std::collections::LinkedList;
 use std::ops::Deref;
 use std::fs;
 use crate::parsing::*;
-use nom::types::CompleteStr as Input;
 
 pub fn build_init_env() -> Env {
     let mut env = Env::new();
-    env.set(String::from("+"), Value::Function(plus));
-    env.set(String::from("*"), Value::Function(mult));
-    env.set(String::from("prn"), Value::Function(prn));
-    env.set(String::from("list"), Value::Function(list));
-    env.set(String::from("list?"), Value::Function(list_p));
-    env.set(String::from("empty?"), Value::Function(empty));
-    env.set(String::from("count"), Value::Function(count));
-    env.set(String::from("read"), Value::Function(read_str));
-    env.set(String::from("="), Value::Function(equals));
-    env.set(String::from("slurp"), Value::Function(slurp));
-    env.set(String::from("str"), Value::Function(string));
-    env.set(String::from("cons"), Value::Function(lisp_cons));
-    env.set(String::from("first"), Value::Function(first));
-    env.set(String::from("rest"), Value::Function(rest));
-    env.set(String::from("mod"), Value::Function(modulo));
+    env.register("+", Arity::AtLeast(0), plus);
+    env.register("-", Arity::AtLeast(1), minus);
+    env.register("*", Arity::AtLeast(0), mult);
+    env.register("/", Arity::AtLeast(1), divide);
+    env.register("<", Arity::AtLeast(1), less);
+    env.register(">", Arity::AtLeast(1), greater);
+    env.register("<=", Arity::AtLeast(1), less_eq);
+    env.register(">=", Arity::AtLeast(1), greater_eq);
+    env.register("abs", Arity::Exactly(1), abs);
+    env.register("min", Arity::AtLeast(1), min);
+    env.register("max", Arity::AtLeast(1), max);
+    env.register("prn", Arity::Exactly(1), prn);
+    env.register("list", Arity::AtLeast(0), list);
+    env.register("list?", Arity::Exactly(1), list_p);
+    env.register("empty?", Arity::Exactly(1), empty);
+    env.register("count", Arity::Exactly(1), count);
+    env.register_fn("read", read_str);
+    env.register("=", Arity::Exactly(2), equals);
+    env.register_fn("slurp", slurp);
+    // Files are loaded into the global environment. The builtin lives there
+    // too, so it holds it weakly rather than keeping it alive forever.
+    let global = env.downgrade();
+    env.register_fn("load-file", move |filename: String| match global.upgrade() {
+        Some(mut global) => load_file(&filename, &mut global),
+        None => Err(Errors::NativeError(String::from("load-file outlived its interpreter"))),
+    });
+    env.register_fn("doc", doc);
+    env.register_fn("throw", |v: Value| -> Result<Value, Errors> { Err(Errors::Thrown(Box::new(v))) });
+    env.register("error", Arity::AtLeast(1), error);
+    env.register_fn("error?", |v: Value| matches!(v, Value::Error(_)));
+    env.register_fn("error-kind", |e: ErrorValue| Value::Symbol(e.kind));
+    env.register_fn("error-message", |e: ErrorValue| e.message);
+    env.register_fn("error-irritants", |e: ErrorValue| e.irritants);
+    env.register_fn("atom", Atom::new);
+    env.register_fn("atom?", |v: Value| matches!(v, Value::Atom(_)));
+    env.register_fn("deref", deref);
+    env.register_fn("reset!", reset);
+    env.register("swap!", Arity::AtLeast(2), swap);
+    env.register_fn("source", source);
+    env.register("gensym", Arity::AtLeast(0), gensym);
+    env.register("str", Arity::AtLeast(0), string);
+    env.register("cons", Arity::Exactly(2), lisp_cons);
+    env.register("first", Arity::Exactly(1), first);
+    env.register("rest", Arity::Exactly(1), rest);
+    env.register("reverse", Arity::Exactly(1), reverse);
+    env.register("mod", Arity::Exactly(2), modulo);
+    env.register("exact->inexact", Arity::Exactly(1), exact_to_inexact);
+    env.register("floor", Arity::Exactly(1), floor);
+    env.register("round", Arity::Exactly(1), round);
+    env.register("sqrt", Arity::Exactly(1), sqrt);
+    env.register("expt", Arity::Exactly(2), expt);
+    env.register("vector", Arity::AtLeast(0), vector);
+    env.register("hash-map", Arity::AtLeast(0), hash_map);
+    env.register("hash-set", Arity::AtLeast(0), hash_set);
+    env.register_fn("vector?", |v: Value| matches!(v, Value::Vector(_)));
+    env.register_fn("map?", |v: Value| matches!(v, Value::Map(_)));
+    env.register_fn("set?", |v: Value| matches!(v, Value::Set(_)));
+    env.register("get", Arity::AtLeast(2), get);
+    env.register("nth", Arity::Exactly(2), nth);
+    env.register("assoc", Arity::AtLeast(3), assoc);
+    env.register("dissoc", Arity::AtLeast(1), dissoc);
+    env.register("conj", Arity::AtLeast(1), conj);
+    env.register("keys", Arity::Exactly(1), keys);
+    env.register("vals", Arity::Exactly(1), vals);
+    env.register("contains?", Arity::Exactly(2), contains);
+    env.register("union", Arity::AtLeast(1), union);
+    env.register("intersection", Arity::AtLeast(1), intersection);
     env
 }
 
 
+fn to_numbers(p: Params) -> Result<Vec<Number>, Errors> {
+    p.iter().map(|b| Number::from_value(b)).collect()
+}
+
+fn number_to_lisp(n: Number) -> FResult {
+    Ok(Box::new(n.into_value()))
+}
+
+// Takes exactly one numeric argument
+fn one_number(p: Params) -> Result<Number, Errors> {
+    if p.len() != 1 {
+        return Err(Errors::ArityError(p.len() as i32, 1));
+    }
+    Number::from_value(p.front().unwrap())
+}
+
 fn plus(p: Params) -> FResult {
-    let mut sum = 0;
-    for b in p {
-        match *b {
-            Value::Int (i) => sum += i,
-            _ => return Err(Errors::TypeError),
-        }
+    let sum = to_numbers(p)?
+        .into_iter()
+        .fold(Number::Int(0), numbers::add);
+    number_to_lisp(sum)
+}
+
+// (- x) negates, (- x y z) subtracts y and z from x
+fn minus(p: Params) -> FResult {
+    let mut nums = to_numbers(p)?.into_iter();
+    let first = match nums.next() {
+        Some(n) => Ok(n),
+        None => Err(Errors::ArityError(0, 1)),
+    }?;
+    if nums.len() == 0 {
+        return number_to_lisp(numbers::neg(first));
     }
-    Ok(Box::new(Value::Int(sum)))
+    number_to_lisp(nums.fold(first, numbers::sub))
 }
 
 fn mult(p: Params) -> FResult {
-    let mut product = 1;
-    for b in p {
-        match *b {
-            Value::Int (i) => product *= i,
-            _ => return Err(Errors::TypeError),
+    let product = to_numbers(p)?
+        .into_iter()
+        .fold(Number::Int(1), numbers::mul);
+    number_to_lisp(product)
+}
+
+// (/ x) is 1 / x, (/ x y z) divides x by y and then z
+fn divide(p: Params) -> FResult {
+    let mut nums = to_numbers(p)?.into_iter();
+    let first = match nums.next() {
+        Some(n) => Ok(n),
+        None => Err(Errors::ArityError(0, 1)),
+    }?;
+    if nums.len() == 0 {
+        return number_to_lisp(numbers::div(Number::Int(1), first)?);
+    }
+    let mut quotient = first;
+    for n in nums {
+        quotient = numbers::div(quotient, n)?;
+    }
+    number_to_lisp(quotient)
+}
+
+// Checks that `ordered` holds between every adjacent pair of arguments
+fn compare(p: Params, ordered: fn(Ordering) -> bool) -> FResult {
+    let nums = to_numbers(p)?;
+    if nums.is_empty() {
+        return Err(Errors::ArityError(0, 1));
+    }
+    native_to_lisp(nums.windows(2).all(|w|
+        numbers::compare(&w[0], &w[1]).is_some_and(ordered)))
+}
+
+fn less(p: Params) -> FResult {
+    compare(p, |o| o == Ordering::Less)
+}
+
+fn greater(p: Params) -> FResult {
+    compare(p, |o| o == Ordering::Greater)
+}
+
+fn less_eq(p: Params) -> FResult {
+    compare(p, |o| o != Ordering::Greater)
+}
+
+fn greater_eq(p: Params) -> FResult {
+    compare(p, |o| o != Ordering::Less)
+}
+
+fn abs(p: Params) -> FResult {
+    number_to_lisp(numbers::abs(one_number(p)?))
+}
+
+// Picks the argument for which `wanted` holds against every other one
+fn extremum(p: Params, wanted: Ordering) -> FResult {
+    let mut nums = to_numbers(p)?.into_iter();
+    let mut best = match nums.next() {
+        Some(n) => Ok(n),
+        None => Err(Errors::ArityError(0, 1)),
+    }?;
+    for n in nums {
+        if numbers::compare(&n, &best) == Some(wanted) {
+            best = n;
         }
     }
-    Ok(Box::new(Value::Int(product)))
+    number_to_lisp(best)
+}
+
+fn min(p: Params) -> FResult {
+    extremum(p, Ordering::Less)
+}
+
+fn max(p: Params) -> FResult {
+    extremum(p, Ordering::Greater)
 }
 
 fn modulo(p: Params) -> FResult {
-    let mut list = p;
-    let a = match list.pop_front() {
-        Some(b) => match b.deref() {
-            Value::Int(i) => Ok(i.clone()),
-            _ => Err(Errors::TypeError),
-        },
-        None => Err(Errors::ArityError(0,2)),
-    }?;
+    if p.len() != 2 {
+        return Err(Errors::ArityError(p.len() as i32, 2));
+    }
+    let mut nums = to_numbers(p)?.into_iter();
+    let a = nums.next().unwrap();
+    let b = nums.next().unwrap();
+    number_to_lisp(numbers::rem(a, b)?)
+}
 
-    let b = match list.pop_front() {
-        Some(b) => match b.deref() {
-            Value::Int(i) => Ok(i.clone()),
-            _ => Err(Errors::TypeError),
-        },
-        None => Err(Errors::ArityError(1,2)),
-    }?;
+fn exact_to_inexact(p: Params) -> FResult {
+    number_to_lisp(numbers::to_inexact(one_number(p)?))
+}
 
-    Ok(Box::new(Value::Int(a % b)))
+fn floor(p: Params) -> FResult {
+    number_to_lisp(numbers::floor(one_number(p)?))
+}
 
+fn round(p: Params) -> FResult {
+    number_to_lisp(numbers::round(one_number(p)?))
+}
+
+fn sqrt(p: Params) -> FResult {
+    number_to_lisp(numbers::sqrt(one_number(p)?)?)
+}
+
+fn expt(p: Params) -> FResult {
+    if p.len() != 2 {
+        return Err(Errors::ArityError(p.len() as i32, 2));
+    }
+    let mut nums = to_numbers(p)?.into_iter();
+    let base = nums.next().unwrap();
+    let power = nums.next().unwrap();
+    number_to_lisp(numbers::expt(base, power)?)
 }
 
 
@@ -76,12 +235,12 @@ fn prn(p: Params) -> FResult {
         Some(v) => Ok(v),
         None => Err(Errors::ArityError(0, 1)),
     }?;
-    println!("{}", toprint.to_string());
+    println!("{}", toprint);
     Ok(Box::new(Value::Nil))
 }
 
 fn list(p: Params) -> FResult {
-    Ok(Box::new(Value::List(p)))
+    Ok(Box::new(Value::List(p.into())))
 }
 
 fn list_p(p: Params) -> FResult {
@@ -103,12 +262,8 @@ fn empty(p: Params) -> FResult {
         None => Err(Errors::ArityError(0, 1)),
     }?;
 
-    let lst = match (*subject).deref() {
-        Value::List(lst) => Ok(lst),
-        _ => Err(Errors::TypeError),
-    }?;
-
-    Ok(Box::new(if lst.len() == 0 {Value::True} else {Value::False}))
+    let len = collection_len(subject)?;
+    Ok(Box::new(if len == 0 {Value::True} else {Value::False}))
 }
 
 fn count(p: Params) -> FResult {
@@ -117,31 +272,12 @@ fn count(p: Params) -> FResult {
         None => Err(Errors::ArityError(0, 1)),
     }?;
 
-    let lst = match (*subject).deref() {
-        Value::List(lst) => Ok(lst),
-        _ => Err(Errors::TypeError),
-    }?;
-
-    Ok(Box::new(Value::Int(lst.len() as i32)))
+    Ok(Box::new(Value::Int(collection_len(subject)? as i64)))
 
 }
 
-fn read_str(p: Params) -> FResult {
-    let subject = match p.front() {
-        Some(v) => Ok(v),
-        None => Err(Errors::ArityError(0, 1)),
-    }?;
-
-    let s = match (*subject).deref() {
-        Value::Str(s) => Ok(s),
-        _ => Err(Errors::TypeError),
-    }?;
-
-    match parse_value(Input(&s)) {
-        Ok(v) => Ok(v.1),
-        _=> Err(Errors::TypeError),
-    }
-        
+fn read_str(s: String) -> FResult {
+    READ(s)
 }
 
 fn equals(p: Params) -> FResult {
@@ -162,21 +298,79 @@ fn native_to_lisp(b : bool) -> FResult {
     Ok(Box::new(if b { Value::True } else { Value::False } ))
 }
 
-fn slurp(p: Params) -> FResult {
-    let filename = match p.front() {
-        Some(s) => Ok(s),
-        None => Err(Errors::ArityError(0, 1)),
-    }?;
-    let filename = match filename.deref() {
-        Value::Str(s) => Ok(s),
-        _ => Err(Errors::TypeError),
-    }?;
+fn slurp(filename: String) -> Result<String, Errors> {
+    match fs::read_to_string(&filename) {
+        Ok(s) => Ok(s),
+        Err(_) => Err(Errors::IOError(filename)),
+    }
+}
+
+pub fn load_file(filename: &str, env: &mut Env) -> FResult {
+    let contents = slurp(String::from(filename))?;
+    load_source(&contents, filename, env)
+}
+
+// Evaluates each form in `contents` in turn, returning the value of the last.
+// `filename` is only used to report errors.
+pub fn load_source(contents: &str, filename: &str, env: &mut Env) -> FResult {
+    let forms = read_all(Source::new(contents, Some(filename)))?;
+    let mut result = Box::new(Value::Nil);
+    for (i, form) in forms.into_iter().enumerate() {
+        result = match EVAL(form, env) {
+            Ok(v) => v,
+            Err(e) => return Err(Errors::Loading(Box::new(e), String::from(filename), i + 1)),
+        };
+    }
+    Ok(result)
+}
+
+// (error message irritants...) makes an error object for `throw`
+fn error(p: Params) -> FResult {
+    let mut args = p.into_iter();
+    let message = String::from_lisp(&args.next().unwrap())?;
+    let irritants = args.map(|v| *v).collect();
+    let e = ErrorValue { kind: String::from("error"), message, irritants };
+    Ok(Box::new(Value::Error(Box::new(e))))
+}
+
+fn deref(a: Atom) -> Value {
+    a.get()
+}
+
+// (reset! atom value) replaces the atom's value, returning the new value
+fn reset(a: Atom, v: Value) -> Value {
+    a.set(v.clone());
+    v
+}
 
-    match fs::read_to_string(filename) {
-        Ok(s) => Ok(Box::new(Value::Str(s))),
-        Err(_) => Err(Errors::IOError(filename.clone())),
+// (swap! atom f args...) sets the atom to (f value args...), returning the new value
+fn swap(p: Params) -> FResult {
+    let mut args = p;
+    let atom = Atom::from_lisp(&args.pop_front().unwrap())?;
+    let f = args.pop_front().unwrap();
+    args.push_front(Box::new(atom.get()));
+    let new = apply(f, args)?;
+    atom.set((*new).clone());
+    Ok(new)
+}
+
+// The docstring a function was defined with, or nil
+fn doc(f: Value) -> Result<Option<String>, Errors> {
+    match f {
+        Value::DynFunc(f) => Ok(f.doc),
+        Value::Function(_) => Ok(None),
+        v => Err(Errors::type_error("function", &v)),
     }
+}
 
+// The text of the form that defined a function, or nil if it wasn't read
+// from source code
+fn source(f: Value) -> Result<Option<String>, Errors> {
+    match f {
+        Value::DynFunc(f) => Ok(f.source.map(|span| String::from(span.text()))),
+        Value::Function(_) => Ok(None),
+        v => Err(Errors::type_error("function", &v)),
+    }
 }
 
 fn string(p: Params) -> FResult {
@@ -184,7 +378,7 @@ fn string(p: Params) -> FResult {
         .into_iter()
         .map(|v| match v.deref() {
             Value::Str(s) => Ok(s.clone()),
-            _ => Err(Errors::TypeError),
+            v => Err(Errors::type_error("string", v)),
         }).collect();
     let strings = strings?;
     let string = strings
@@ -196,6 +390,7 @@ fn string(p: Params) -> FResult {
     Ok(Box::new(Value::Str(string)))
 }
 
+// The new list shares `rest` rather than copying it
 fn lisp_cons(p: Params) -> FResult {
     if p.len() != 2 {
         return Err(Errors::ArityError(p.len() as i32, 2));
@@ -203,24 +398,14 @@ fn lisp_cons(p: Params) -> FResult {
     let mut p = p;
     let first = p.pop_front().unwrap();
     let rest = p.pop_front().unwrap();
-        
-    let new  = match rest.deref() {
-        Value::List(lst) => Ok(cons(first.deref().clone(), lst.deref().clone())),
-        _ => Err(Errors::TypeError),
-    }?;
 
-    Ok(Box::new(Value::List(new)))
-
-}
-
-fn cons(first: Value, rest: LinkedList<RValue>) -> LinkedList<RValue>{
-    let mut n = LinkedList::new();
-    n.push_front(Box::new(first));
-    for v in rest {
-        n.push_back(v);
+    match *rest {
+        Value::List(mut lst) => {
+            lst.push_front(first);
+            Ok(Box::new(Value::List(lst)))
+        },
+        v => Err(Errors::type_error("list", &v)),
     }
-    
-    n
 }
 
 fn first(p: Params) -> FResult {
@@ -230,7 +415,7 @@ fn first(p: Params) -> FResult {
                 Some(v) => Ok(v.clone()),
                 None => Ok(Box::new(Value::Nil)),
             }
-            _ => Err(Errors::TypeError),
+            v => Err(Errors::type_error("list", v)),
         }
 
         None => Err(Errors::ArityError(0, 1))
@@ -240,16 +425,269 @@ fn first(p: Params) -> FResult {
 fn rest(p: Params) -> FResult {
     match p.front() {
         Some(b) => match b.deref() {
-            Value::List(lst) => {
-                let mut lst = lst.clone();
-                lst.pop_front();
-                Ok(Box::new(Value::List(lst)))   
-            },
-            _ => Err(Errors::TypeError),
+            Value::List(lst) => Ok(Box::new(Value::List(lst.tail()))),
+            v => Err(Errors::type_error("list", v)),
         }
         _ => Err(Errors::ArityError(0,1)),
     }
         
 }
 
-    
+fn reverse(p: Params) -> FResult {
+    match p.front() {
+        Some(b) => match b.deref() {
+            Value::List(lst) => {
+                let mut reversed = List::new();
+                for v in lst.iter() {
+                    reversed.push_front(v.clone());
+                }
+                Ok(Box::new(Value::List(reversed)))
+            },
+            v => Err(Errors::type_error("list", v)),
+        }
+        None => Err(Errors::ArityError(0,1)),
+    }
+}
+
+// (gensym) or (gensym "prefix") makes a symbol no other symbol is equal to
+fn gensym(p: Params) -> FResult {
+    let prefix = match p.len() {
+        0 => String::from("G"),
+        1 => String::from_lisp(p.front().unwrap())?,
+        n => return Err(Errors::ArityError(n as i32, 1)),
+    };
+    Ok(Box::new(Value::Symbol(syntax::gensym(&prefix))))
+}
+
+fn collection_len(v: &Value) -> Result<usize, Errors> {
+    match v {
+        Value::List(lst) => Ok(lst.len()),
+        Value::Vector(vector) => Ok(vector.len()),
+        Value::Map(map) => Ok(map.len()),
+        Value::Set(set) => Ok(set.len()),
+        v => Err(Errors::type_error("collection", v)),
+    }
+}
+
+fn vector(p: Params) -> FResult {
+    Ok(Box::new(Value::Vector(p.into_iter().map(|v| *v).collect())))
+}
+
+// (hash-map k1 v1 k2 v2...)
+fn hash_map(p: Params) -> FResult {
+    if !p.len().is_multiple_of(2) {
+        return Err(Errors::ArityError(p.len() as i32, p.len() as i32 + 1));
+    }
+    let mut map = collections::Map::new();
+    let mut args = p.into_iter();
+    while let (Some(k), Some(v)) = (args.next(), args.next()) {
+        map.insert(Key::new(*k)?, *v);
+    }
+    Ok(Box::new(Value::Map(map)))
+}
+
+fn hash_set(p: Params) -> FResult {
+    let set = p.into_iter().map(|v| Key::new(*v)).collect::<Result<_, _>>()?;
+    Ok(Box::new(Value::Set(set)))
+}
+
+// The position `index` refers to in a sequence of length `len`
+fn position(index: &Value, len: usize) -> Result<usize, Errors> {
+    let i = i64::from_lisp(index)?;
+    if i < 0 || i as usize >= len {
+        return Err(Errors::IndexError(i, len));
+    }
+    Ok(i as usize)
+}
+
+// (get coll key default) looks up a map's key, a set's member or a vector's
+// index, giving `default` (or nil) if it isn't there
+fn get(p: Params) -> FResult {
+    if p.len() > 3 {
+        return Err(Errors::ArityError(p.len() as i32, 3));
+    }
+    let mut args = p.into_iter();
+    let coll = args.next().unwrap();
+    let key = args.next().unwrap();
+    let default = args.next().unwrap_or_else(|| Box::new(Value::Nil));
+    let found = match *coll {
+        Value::Map(map) => map.get(&Key::new(*key)?).cloned(),
+        Value::Set(set) => {
+            let key = Key::new(*key)?;
+            if set.contains(&key) { Some(key.into_value()) } else { None }
+        },
+        Value::Vector(vector) => match *key {
+            Value::Int(i) if i >= 0 => vector.get(i as usize).cloned(),
+            _ => None,
+        },
+        Value::Nil => None,
+        v => return Err(Errors::type_error("map, set or vector", &v)),
+    };
+    Ok(found.map_or(default, Box::new))
+}
+
+fn nth(p: Params) -> FResult {
+    let coll = p.front().unwrap();
+    let index = p.back().unwrap();
+    match coll.deref() {
+        Value::List(lst) => Ok(lst.iter().nth(position(index, lst.len())?).unwrap().clone()),
+        Value::Vector(vector) => Ok(Box::new(vector[position(index, vector.len())?].clone())),
+        v => Err(Errors::type_error("list or vector", v)),
+    }
+}
+
+// (assoc coll k1 v1 k2 v2...) sets keys of a map, or indexes of a vector;
+// the index just past the end appends
+fn assoc(p: Params) -> FResult {
+    if p.len().is_multiple_of(2) {
+        return Err(Errors::ArityError(p.len() as i32, p.len() as i32 + 1));
+    }
+    let mut args = p.into_iter();
+    let coll = args.next().unwrap();
+    let mut pairs = Vec::new();
+    while let (Some(k), Some(v)) = (args.next(), args.next()) {
+        pairs.push((*k, *v));
+    }
+    match *coll {
+        Value::Map(mut map) => {
+            for (k, v) in pairs {
+                map.insert(Key::new(k)?, v);
+            }
+            Ok(Box::new(Value::Map(map)))
+        },
+        Value::Nil => assoc_new(pairs),
+        Value::Vector(mut vector) => {
+            for (i, v) in pairs {
+                let len = vector.len();
+                if i == Value::Int(len as i64) {
+                    vector.push_back(v);
+                } else {
+                    vector[position(&i, len)?] = v;
+                }
+            }
+            Ok(Box::new(Value::Vector(vector)))
+        },
+        v => Err(Errors::type_error("map or vector", &v)),
+    }
+}
+
+fn assoc_new(pairs: Vec<(Value, Value)>) -> FResult {
+    let map = pairs.into_iter().map(|(k, v)| Ok((Key::new(k)?, v))).collect::<Result<_, Errors>>()?;
+    Ok(Box::new(Value::Map(map)))
+}
+
+// (dissoc coll keys...) removes keys from a map, or members from a set
+fn dissoc(p: Params) -> FResult {
+    let mut args = p.into_iter();
+    let coll = args.next().unwrap();
+    match *coll {
+        Value::Map(mut map) => {
+            for k in args {
+                map.remove(&Key::new(*k)?);
+            }
+            Ok(Box::new(Value::Map(map)))
+        },
+        Value::Set(mut set) => {
+            for k in args {
+                set.remove(&Key::new(*k)?);
+            }
+            Ok(Box::new(Value::Set(set)))
+        },
+        Value::Nil => Ok(Box::new(Value::Nil)),
+        v => Err(Errors::type_error("map or set", &v)),
+    }
+}
+
+// (conj coll items...) adds items where they go most cheaply: the front of
+// a list, the end of a vector. Items added to a map are `[key value]` pairs.
+fn conj(p: Params) -> FResult {
+    let mut args = p.into_iter();
+    let coll = args.next().unwrap();
+    match *coll {
+        Value::List(mut lst) => {
+            for v in args {
+                lst.push_front(v);
+            }
+            Ok(Box::new(Value::List(lst)))
+        },
+        Value::Nil => {
+            let mut lst = LinkedList::new();
+            for v in args {
+                lst.push_front(v);
+            }
+            Ok(Box::new(Value::List(lst.into())))
+        },
+        Value::Vector(mut vector) => {
+            vector.extend(args.map(|v| *v));
+            Ok(Box::new(Value::Vector(vector)))
+        },
+        Value::Set(mut set) => {
+            for v in args {
+                set.insert(Key::new(*v)?);
+            }
+            Ok(Box::new(Value::Set(set)))
+        },
+        Value::Map(mut map) => {
+            for entry in args {
+                let (k, v) = match *entry {
+                    Value::Vector(pair) if pair.len() == 2 => {
+                        let mut pair = pair.into_iter();
+                        (pair.next().unwrap(), pair.next().unwrap())
+                    },
+                    v => <(Value, Value)>::from_lisp(&v)?,
+                };
+                map.insert(Key::new(k)?, v);
+            }
+            Ok(Box::new(Value::Map(map)))
+        },
+        v => Err(Errors::type_error("collection", &v)),
+    }
+}
+
+fn keys(p: Params) -> FResult {
+    match p.front().unwrap().deref() {
+        Value::Map(map) => Ok(Box::new(map.keys().map(|k| k.value().clone()).collect::<Vec<_>>().to_lisp()?)),
+        v => Err(Errors::type_error("map", v)),
+    }
+}
+
+fn vals(p: Params) -> FResult {
+    match p.front().unwrap().deref() {
+        Value::Map(map) => Ok(Box::new(map.values().cloned().collect::<Vec<_>>().to_lisp()?)),
+        v => Err(Errors::type_error("map", v)),
+    }
+}
+
+// (contains? coll key) is whether a map has a key, a set has a member, or a
+// vector has an index
+fn contains(p: Params) -> FResult {
+    let coll = p.front().unwrap();
+    let key = p.back().unwrap();
+    let found = match coll.deref() {
+        Value::Map(map) => map.contains_key(&Key::new((**key).clone())?),
+        Value::Set(set) => set.contains(&Key::new((**key).clone())?),
+        Value::Vector(vector) => matches!(**key, Value::Int(i) if i >= 0 && (i as usize) < vector.len()),
+        Value::Nil => false,
+        v => return Err(Errors::type_error("map, set or vector", v)),
+    };
+    Ok(Box::new(found.to_lisp()?))
+}
+
+fn to_sets(p: Params) -> Result<Vec<collections::Set>, Errors> {
+    p.into_iter().map(|v| match *v {
+        Value::Set(set) => Ok(set),
+        v => Err(Errors::type_error("set", &v)),
+    }).collect()
+}
+
+fn union(p: Params) -> FResult {
+    let mut sets = to_sets(p)?.into_iter();
+    let first = sets.next().unwrap();
+    Ok(Box::new(Value::Set(sets.fold(first, |all, set| all.union(set)))))
+}
+
+fn intersection(p: Params) -> FResult {
+    let mut sets = to_sets(p)?.into_iter();
+    let first = sets.next().unwrap();
+    Ok(Box::new(Value::Set(sets.fold(first, |common, set| common.intersection(set)))))
+}
@@ -0,0 +1,129 @@
+use crate::*;
+use std::cmp::Ordering;
+
+// Persistent collections: a changed copy shares most of its structure with
+// the original, so `assoc`, `conj` and friends are O(log n)
+pub type Vector = im_rc::Vector<Value>;
+pub type Map = im_rc::OrdMap<Key, Value>;
+pub type Set = im_rc::OrdSet<Key>;
+
+// A map key or set member. Keys are kept in order, so a map prints the same
+// way however it was built. Numbers compare by value, as `=` does, so `1`
+// and `1.0` are the same key.
+#[derive(Clone, Debug)]
+pub struct Key (Value);
+
+impl Key {
+    // Functions, atoms and errors can't be keys: they have no order, and
+    // atoms are only equal to themselves
+    pub fn new(v: Value) -> Result<Key, Errors> {
+        check_key(&v)?;
+        Ok(Key(v))
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
+
+    pub fn into_value(self) -> Value {
+        self.0
+    }
+}
+
+fn check_key(v: &Value) -> Result<(), Errors> {
+    match v {
+        Value::Function(_) | Value::DynFunc(_) | Value::Atom(_) | Value::Error(_) =>
+            Err(Errors::type_error("a value that can be a key", v)),
+        Value::List(lst) => lst.iter().try_for_each(|v| check_key(v)),
+        Value::Vector(vector) => vector.iter().try_for_each(check_key),
+        Value::Map(map) => map.values().try_for_each(check_key),
+        _ => Ok(()),
+    }
+}
+
+// Values of different types are ordered by type
+fn rank(v: &Value) -> u8 {
+    match v {
+        Value::Nil => 0,
+        Value::False | Value::True => 1,
+        v if v.is_number() => 2,
+        Value::Str(_) => 3,
+        Value::Symbol(_) => 4,
+        Value::List(_) => 5,
+        Value::Vector(_) => 6,
+        Value::Map(_) => 7,
+        Value::Set(_) => 8,
+        _ => 9,
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        _ if rank(a) != rank(b) => rank(a).cmp(&rank(b)),
+        // NaN isn't ordered against anything, so it goes after every other
+        // number; otherwise it would be equal to them all
+        (a, b) if is_nan(a) || is_nan(b) => is_nan(a).cmp(&is_nan(b)),
+        (a, b) if a.is_number() => match (Number::from_value(a), Number::from_value(b)) {
+            (Ok(a), Ok(b)) => numbers::compare(&a, &b).unwrap_or(Ordering::Equal),
+            _ => Ordering::Equal,
+        },
+        (Value::True, Value::True) | (Value::False, Value::False) => Ordering::Equal,
+        (Value::False, Value::True) => Ordering::Less,
+        (Value::True, Value::False) => Ordering::Greater,
+        (Value::Str(a), Value::Str(b)) | (Value::Symbol(a), Value::Symbol(b)) => a.cmp(b),
+        (Value::List(a), Value::List(b)) =>
+            compare_all(a.iter().map(|v| &**v), b.iter().map(|v| &**v)),
+        (Value::Vector(a), Value::Vector(b)) => compare_all(a.iter(), b.iter()),
+        (Value::Map(a), Value::Map(b)) =>
+            compare_all(a.iter().flat_map(|(k, v)| vec![&k.0, v]), b.iter().flat_map(|(k, v)| vec![&k.0, v])),
+        (Value::Set(a), Value::Set(b)) => compare_all(a.iter().map(|k| &k.0), b.iter().map(|k| &k.0)),
+        // Special forms, which can only be keys in quoted data
+        (a, b) => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+fn is_nan(v: &Value) -> bool {
+    matches!(v, Value::Float(f) if f.is_nan())
+}
+
+fn compare_all<'a>(a: impl Iterator<Item = &'a Value>, b: impl Iterator<Item = &'a Value>) -> Ordering {
+    let mut a = a;
+    let mut b = b;
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => match compare(x, y) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            },
+        }
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Key) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Key {}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Key) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Key) -> Ordering {
+        compare(&self.0, &other.0)
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{}", self.0)
+    }
+}
@@ -0,0 +1,303 @@
+use crate::*;
+use crate::numbers::Number;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::Hash;
+
+// Conversion of a Rust value into a Lisp one, failing only for values Lisp
+// can't hold, such as a map keyed by functions
+pub trait ToLisp {
+    fn to_lisp(self) -> Result<Value, Errors>;
+}
+
+// Conversion of a Lisp value into a Rust one, failing on a type mismatch
+pub trait FromLisp: Sized {
+    fn from_lisp(v: &Value) -> Result<Self, Errors>;
+}
+
+impl ToLisp for Value {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        Ok(self)
+    }
+}
+
+impl FromLisp for Value {
+    fn from_lisp(v: &Value) -> Result<Value, Errors> {
+        Ok(v.clone())
+    }
+}
+
+impl<T: ToLisp> ToLisp for Box<T> {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        (*self).to_lisp()
+    }
+}
+
+macro_rules! integer_conversions {
+    ($($t:ty),*) => {$(
+        impl ToLisp for $t {
+            fn to_lisp(self) -> Result<Value, Errors> {
+                match i64::try_from(self) {
+                    Ok(i) => Ok(Value::Int(i)),
+                    Err(_) => Ok(Value::BigInt(BigInt::from(self))),
+                }
+            }
+        }
+
+        impl FromLisp for $t {
+            fn from_lisp(v: &Value) -> Result<$t, Errors> {
+                let converted = match v {
+                    Value::Int(i) => <$t>::try_from(*i).ok(),
+                    Value::BigInt(i) => <$t>::try_from(i).ok(),
+                    _ => None,
+                };
+                match converted {
+                    Some(i) => Ok(i),
+                    None => Err(Errors::type_error(stringify!($t), v)),
+                }
+            }
+        }
+    )*}
+}
+
+integer_conversions!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl ToLisp for BigInt {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        Ok(Number::Big(self).into_value())
+    }
+}
+
+impl FromLisp for BigInt {
+    fn from_lisp(v: &Value) -> Result<BigInt, Errors> {
+        match v {
+            Value::Int(i) => Ok(BigInt::from(*i)),
+            Value::BigInt(i) => Ok(i.clone()),
+            _ => Err(Errors::type_error("integer", v)),
+        }
+    }
+}
+
+impl ToLisp for f64 {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        Ok(Value::Float(self))
+    }
+}
+
+// Any number converts to a float, exact ones losing precision if need be
+impl FromLisp for f64 {
+    fn from_lisp(v: &Value) -> Result<f64, Errors> {
+        match v {
+            Value::Float(f) => Ok(*f),
+            Value::Int(i) => Ok(*i as f64),
+            Value::BigInt(i) => i.to_f64().ok_or_else(|| Errors::type_error("float", v)),
+            Value::Rational(r) => r.to_f64().ok_or_else(|| Errors::type_error("float", v)),
+            _ => Err(Errors::type_error("number", v)),
+        }
+    }
+}
+
+impl ToLisp for bool {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        Ok(if self { Value::True } else { Value::False })
+    }
+}
+
+impl FromLisp for bool {
+    fn from_lisp(v: &Value) -> Result<bool, Errors> {
+        match v {
+            Value::True => Ok(true),
+            Value::False => Ok(false),
+            _ => Err(Errors::type_error("boolean", v)),
+        }
+    }
+}
+
+impl ToLisp for String {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        Ok(Value::Str(self))
+    }
+}
+
+impl ToLisp for &str {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        Ok(Value::Str(String::from(self)))
+    }
+}
+
+impl FromLisp for String {
+    fn from_lisp(v: &Value) -> Result<String, Errors> {
+        match v {
+            Value::Str(s) => Ok(s.clone()),
+            _ => Err(Errors::type_error("string", v)),
+        }
+    }
+}
+
+impl ToLisp for Atom {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        Ok(Value::Atom(self))
+    }
+}
+
+impl FromLisp for Atom {
+    fn from_lisp(v: &Value) -> Result<Atom, Errors> {
+        match v {
+            Value::Atom(a) => Ok(a.clone()),
+            _ => Err(Errors::type_error("atom", v)),
+        }
+    }
+}
+
+impl FromLisp for ErrorValue {
+    fn from_lisp(v: &Value) -> Result<ErrorValue, Errors> {
+        match v {
+            Value::Error(e) => Ok((**e).clone()),
+            _ => Err(Errors::type_error("error", v)),
+        }
+    }
+}
+
+impl ToLisp for () {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        Ok(Value::Nil)
+    }
+}
+
+// `None` is `nil`
+impl<T: ToLisp> ToLisp for Option<T> {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        match self {
+            Some(v) => v.to_lisp(),
+            None => Ok(Value::Nil),
+        }
+    }
+}
+
+impl<T: FromLisp> FromLisp for Option<T> {
+    fn from_lisp(v: &Value) -> Result<Option<T>, Errors> {
+        match v {
+            Value::Nil => Ok(None),
+            v => Ok(Some(T::from_lisp(v)?)),
+        }
+    }
+}
+
+impl<T: ToLisp> ToLisp for Vec<T> {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        Ok(Value::List(self.into_iter().map(|v| v.to_lisp().map(Box::new)).collect::<Result<_, _>>()?))
+    }
+}
+
+impl<T: FromLisp> FromLisp for Vec<T> {
+    fn from_lisp(v: &Value) -> Result<Vec<T>, Errors> {
+        match v {
+            Value::List(lst) => lst.iter().map(|v| T::from_lisp(v)).collect(),
+            Value::Vector(vector) => vector.iter().map(T::from_lisp).collect(),
+            _ => Err(Errors::type_error("list", v)),
+        }
+    }
+}
+
+// Maps convert to and from `Value::Map`. Converting a map whose keys can't
+// be map keys, such as functions, is a type error.
+impl<K: ToLisp, V: ToLisp> ToLisp for HashMap<K, V> {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        Ok(Value::Map(self.into_iter()
+                      .map(|(k, v)| Ok((Key::new(k.to_lisp()?)?, v.to_lisp()?)))
+                      .collect::<Result<_, Errors>>()?))
+    }
+}
+
+impl<K: FromLisp + Eq + Hash, V: FromLisp> FromLisp for HashMap<K, V> {
+    fn from_lisp(v: &Value) -> Result<HashMap<K, V>, Errors> {
+        match v {
+            Value::Map(map) => map.iter().map(|(k, v)| Ok((K::from_lisp(k.value())?, V::from_lisp(v)?))).collect(),
+            _ => Err(Errors::type_error("map", v)),
+        }
+    }
+}
+
+// Tuples are fixed length lists
+macro_rules! tuple_conversions {
+    ($len:expr; $($t:ident),*) => {
+        impl<$($t: ToLisp),*> ToLisp for ($($t,)*) {
+            #[allow(non_snake_case)]
+            fn to_lisp(self) -> Result<Value, Errors> {
+                let ($($t,)*) = self;
+                Ok(Value::List(vec![$(Box::new($t.to_lisp()?)),*].into_iter().collect()))
+            }
+        }
+
+        impl<$($t: FromLisp),*> FromLisp for ($($t,)*) {
+            fn from_lisp(v: &Value) -> Result<($($t,)*), Errors> {
+                match v {
+                    Value::List(lst) if lst.len() == $len => {
+                        let mut items = lst.iter();
+                        Ok(($($t::from_lisp(items.next().unwrap())?,)*))
+                    },
+                    _ => Err(Errors::type_error(concat!("list of length ", $len), v)),
+                }
+            }
+        }
+    }
+}
+
+tuple_conversions!(1; A);
+tuple_conversions!(2; A, B);
+tuple_conversions!(3; A, B, C);
+tuple_conversions!(4; A, B, C, D);
+
+// What a native function may return: a plain value, or a `Result` whose
+// error converts into `Errors`
+pub trait IntoLispResult {
+    fn into_lisp_result(self) -> FResult;
+}
+
+impl<T: ToLisp> IntoLispResult for T {
+    fn into_lisp_result(self) -> FResult {
+        Ok(Box::new(self.to_lisp()?))
+    }
+}
+
+impl<T: ToLisp, E: Into<Errors>> IntoLispResult for Result<T, E> {
+    fn into_lisp_result(self) -> FResult {
+        match self {
+            Ok(v) => Ok(Box::new(v.to_lisp()?)),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// Wraps an ordinary Rust function as a builtin, deriving its arity from the
+// signature and converting each argument with `FromLisp`
+pub trait IntoBuiltin<Args> {
+    fn into_builtin(self, name: &str) -> Builtin;
+}
+
+macro_rules! builtin_wrappers {
+    ($len:expr; $($t:ident),*) => {
+        impl<Func, R, $($t),*> IntoBuiltin<($($t,)*)> for Func
+            where Func: Fn($($t),*) -> R + 'static,
+                  R: IntoLispResult,
+                  $($t: FromLisp),* {
+            #[allow(unused_mut, unused_variables)]
+            fn into_builtin(self, name: &str) -> Builtin {
+                Builtin::new(name, Arity::Exactly($len), move |p: Params| {
+                    let mut args = p.iter();
+                    self($($t::from_lisp(args.next().unwrap())?),*).into_lisp_result()
+                })
+            }
+        }
+    }
+}
+
+builtin_wrappers!(0;);
+builtin_wrappers!(1; A);
+builtin_wrappers!(2; A, B);
+builtin_wrappers!(3; A, B, C);
+builtin_wrappers!(4; A, B, C, D);
+builtin_wrappers!(5; A, B, C, D, E);
+builtin_wrappers!(6; A, B, C, D, E, F);
@@ -1,23 +1,80 @@
-use rlisp::*;
+use crate::*;
 use std::collections::LinkedList;
 use std::ops::Deref;
 
-#[allow(non_snake_case)]
+// The result of evaluating one form. Forms with an expression in tail
+// position hand it back to `EVAL` instead of recursing, so that loop can
+// continue with it without growing the Rust stack.
+// A call to a function hands back its body along with the function's name,
+// which `EVAL` keeps for the backtrace should the body fail.
+pub enum Step {
+    Done(RValue),
+    Tail(RValue, Env),
+    Call(RValue, Env, String),
+}
+
+#[allow(non_snake_case, clippy::boxed_local)]
 pub fn EVAL(input:RValue, env: &mut Env) -> FResult{
-    match *input {
-        Value::List(lst) =>
-            if lst.len() == 0 {
-                Ok(Box::new(Value::List(lst)))
-            } else{
-                eval_list(lst, env)
+    let mut ast = input;
+    let mut env = env.clone();
+    // The function whose body this loop is evaluating, if any
+    let mut frame: Option<Frame> = None;
+    // The innermost source location seen; forms built by macros or `eval`
+    // have none of their own and are reported at the form they came from
+    let mut span: Option<Span> = None;
+    loop {
+        let step = match *ast {
+            Value::List(lst) =>
+                if lst.is_empty() {
+                    Ok(Step::Done(Box::new(Value::List(lst))))
+                } else{
+                    if lst.span.is_some() {
+                        span = lst.span.clone();
+                    }
+                    eval_list(lst, &mut env)
+                },
+            other => eval_ast(other, &mut env).map(Step::Done),
+        };
+
+        match step {
+            Ok(Step::Done(v)) => return Ok(v),
+            Ok(Step::Tail(next, scope)) => {
+                ast = next;
+                env = scope;
+            },
+            Ok(Step::Call(body, scope, name)) => {
+                frame = Some(Frame { name, call_site: span.clone() });
+                ast = body;
+                env = scope;
             },
-        other => eval_ast(other, env) 
+            Err(e) => return Err(add_trace(e, span, frame)),
+        }
     }
+}
 
+// Records where an error passed through on its way out of `EVAL`. The
+// innermost location is kept, and each function body adds a frame.
+fn add_trace(e: Errors, span: Option<Span>, frame: Option<Frame>) -> Errors {
+    let (e, mut trace) = match e {
+        Errors::Traced(e, trace) => (e, trace),
+        e => (Box::new(e), Trace::default()),
+    };
+    if trace.span.is_none() {
+        trace.span = span;
+    }
+    if let Some(frame) = frame {
+        trace.backtrace.push(frame);
+    }
+    if trace.span.is_none() && trace.backtrace.is_empty() {
+        *e
+    } else {
+        Errors::Traced(e, trace)
+    }
 }
 
-pub fn eval_list(input: LinkedList<RValue>, env: &mut Env) -> FResult{
-    let list = input;
+pub fn eval_list(input: List, env: &mut Env) -> Result<Step, Errors>{
+    let span = input.span.clone();
+    let list = input.into_items();
 
     let first = match list.front() {
         Some(b) => b,
@@ -26,61 +83,202 @@ pub fn eval_list(input: LinkedList<RValue>, env: &mut Env) -> FResult{
 
     // Keyword detection here
     match first.deref() {
-        Value::Define => eval_define(list, env),
-        Value::Defmacro => eval_defmacro(list, env),
-        Value::Let => eval_let(list, env),
+        Value::Define => eval_define(list, env).map(Step::Done),
+        Value::Defmacro => eval_defmacro(list, env).map(Step::Done),
+        Value::Macroexpand => eval_macroexpand(list, env).map(Step::Done),
+        Value::Macroexpand1 => eval_macroexpand_1(list, env).map(Step::Done),
+        Value::Let | Value::LetStar | Value::Letrec => eval_let(list, env),
         Value::Do => eval_do(list, env),
         Value::If => eval_if(list, env),
-        Value::Fn => eval_fn(list),
+        Value::Fn => eval_fn(list, span, env).map(Step::Done),
+        Value::Defn => eval_defn(list, span, env).map(Step::Done),
+        Value::And | Value::Or => eval_and_or(list, env),
+        Value::Cond => eval_cond(list, env),
+        Value::Case => eval_case(list, env),
+        Value::When | Value::Unless => eval_when(list, env),
         Value::Eval => eval_eval(list, env),
-        Value::Quote => eval_quote(list),
-        Value::Quasiquote => eval_qquote(list, env),
-        Value::Env => eval_env(list, env),
-        _ => handle_function(recr_eval_list(list, env)?, env),
+        Value::Quote => eval_quote(list).map(Step::Done),
+        Value::Quasiquote => eval_qquote(list, env).map(Step::Done),
+        Value::Env => eval_env(list, env).map(Step::Done),
+        Value::SetBang => eval_set(list, env).map(Step::Done),
+        Value::Try => eval_try(list, env).map(Step::Done),
+        Value::DefineSyntax => eval_define_syntax(list, env).map(Step::Done),
+        Value::SyntaxRules => eval_syntax_rules(list, env).map(Step::Done),
+        _ => eval_application(list, env),
     }
 
 }
 
-fn eval_env(list: LinkedList<RValue>, env: &mut Env) -> FResult {
+fn eval_application(list: LinkedList<RValue>, env: &mut Env) -> Result<Step, Errors> {
+    let mut list = list;
+    let head = list.pop_front().unwrap();
+    let name = match head.deref() {
+        Value::Symbol(s) => s.clone(),
+        _ => String::from("<anonymous>"),
+    };
+    let head = EVAL(head, env)?;
+    match head.deref() {
+        // Macros receive their arguments unevaluated, and the form they
+        // produce is evaluated in the caller's scope
+        Value::DynFunc(f) if f.is_macro => {
+            let expansion = expand_macro(head, list)?;
+            Ok(Step::Tail(expansion, env.clone()))
+        },
+        _ => {
+            let mut args = recr_eval_list(list, env)?;
+            args.push_front(head);
+            handle_function(args, &name)
+        },
+    }
+}
+
+fn expand_macro(mac: RValue, args: LinkedList<RValue>) -> FResult {
+    let mut call = args;
+    call.push_front(mac);
+    match handle_dyn_function(call, "macro")? {
+        Step::Tail(body, mut scope) | Step::Call(body, mut scope, _) =>
+            EVAL(body, &mut scope),
+        Step::Done(v) => Ok(v),
+    }
+}
+
+// Expands `form` once if it is a call to a macro, returning whether it did
+fn macroexpand_1(form: RValue, env: &Env) -> Result<(RValue, bool), Errors> {
+    let mac = match form.deref() {
+        Value::List(lst) => match lst.front().map(|b| b.deref()) {
+            Some(Value::Symbol(s)) => match env.lookup(s.clone()) {
+                Ok(v) => match v.deref() {
+                    Value::DynFunc(f) if f.is_macro => Some(v),
+                    _ => None,
+                },
+                Err(_) => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    };
+
+    match (mac, *form) {
+        (Some(mac), Value::List(mut lst)) => {
+            lst.pop_front(); // Remove the macro's name
+            Ok((expand_macro(mac, lst.into_items())?, true))
+        },
+        (_, form) => Ok((Box::new(form), false)),
+    }
+}
+
+fn eval_macroexpand_1(list: LinkedList<RValue>, env: &mut Env) -> FResult {
+    let mut list = list;
+    list.pop_front(); // Remove `macroexpand-1`
+    let form = match list.pop_front() {
+        Some(b) => Ok(b),
+        None => Err(Errors::ArityError(0, 1)),
+    }?;
+    Ok(macroexpand_1(form, env)?.0)
+}
+
+fn eval_macroexpand(list: LinkedList<RValue>, env: &mut Env) -> FResult {
+    let mut list = list;
+    list.pop_front(); // Remove `macroexpand`
+    let mut form = match list.pop_front() {
+        Some(b) => Ok(b),
+        None => Err(Errors::ArityError(0, 1)),
+    }?;
+    loop {
+        let (expanded, changed) = macroexpand_1(form, env)?;
+        if !changed {
+            return Ok(expanded);
+        }
+        form = expanded;
+    }
+}
+
+fn eval_env(_list: LinkedList<RValue>, env: &mut Env) -> FResult {
     Ok(Box::new(env.unwrap()))
 }
 
 fn eval_qquote(list: LinkedList<RValue>, env: &mut Env) -> FResult {
     let mut list = list;
     list.pop_front(); // Remove `qquote`
-    let target = list.pop_front().unwrap();
-    recr_qquote(target, env)
-}
-
-fn recr_qquote(val: RValue, env: &mut Env) -> FResult {
-    match val.deref() {
-        Value::List(list) => {
-            let list = list.clone();
-            if list.len() == 0 {
-                Ok(Box::new(Value::List(list)))
-            } else { // Check for unquotes
-                let mut list = list;
-                let first = list.front().unwrap();
-                match first.deref() {
-                    Value::Unquote => { // Do evaluate
-                        list.pop_front();
-                        let target = list.pop_front().unwrap();
-                        EVAL(target, env)
-                    },
-                    // recurse to look for unquotes
-                    _ => {
-                        let checked: Result<LinkedList<RValue>, Errors>  = list
-                            .into_iter()
-                            .map(
-                                |expr| recr_qquote(expr, env))
-                            .collect();
-                        Ok(Box::new(Value::List(checked?)))
-                    }
-                }
+    let target = match list.pop_front() {
+        Some(target) => Ok(target),
+        None => Err(Errors::FormError(String::from("quasiquote expects an expression"))),
+    }?;
+    recr_qquote(target, env, 1)
+}
+
+// The form `v` wraps if it is `(keyword form)`
+fn quoted_by(v: &Value, keyword: &Value) -> Option<RValue> {
+    match v {
+        Value::List(lst) if lst.len() == 2 && lst.front().map(|b| b.deref()) == Some(keyword) =>
+            lst.iter().nth(1).cloned(),
+        _ => None,
+    }
+}
+
+fn wrap(keyword: Value, form: RValue) -> RValue {
+    Box::new(Value::List(vec![Box::new(keyword), form].into_iter().collect()))
+}
+
+// `depth` counts the quasiquotes around `val` that no unquote has cancelled.
+// Unquotes at depth 1 are evaluated; deeper ones are kept, with what they
+// contain processed a level down.
+fn recr_qquote(val: RValue, env: &mut Env, depth: usize) -> FResult {
+    if let Some(form) = quoted_by(&val, &Value::Unquote) {
+        return if depth == 1 {
+            EVAL(form, env)
+        } else {
+            Ok(wrap(Value::Unquote, recr_qquote(form, env, depth - 1)?))
+        };
+    }
+    if let Some(form) = quoted_by(&val, &Value::UnquoteSplicing) {
+        return if depth == 1 {
+            Err(Errors::FormError(String::from("unquote-splicing must be inside a list")))
+        } else {
+            Ok(wrap(Value::UnquoteSplicing, recr_qquote(form, env, depth - 1)?))
+        };
+    }
+    if let Some(form) = quoted_by(&val, &Value::Quasiquote) {
+        return Ok(wrap(Value::Quasiquote, recr_qquote(form, env, depth + 1)?));
+    }
+
+    // Collections are walked like lists, so `[,x]` and `{:a ,x}` fill in x
+    match *val {
+        Value::List(list) => Ok(Box::new(Value::List(qquote_items(list, env, depth)?.into()))),
+        Value::Vector(vector) => {
+            let items = qquote_items(vector.into_iter().map(Box::new), env, depth)?;
+            Ok(Box::new(Value::Vector(items.into_iter().map(|v| *v).collect())))
+        },
+        Value::Set(set) => {
+            let items = qquote_items(set.into_iter().map(|k| Box::new(k.into_value())), env, depth)?;
+            Ok(Box::new(Value::Set(items.into_iter().map(|v| Key::new(*v)).collect::<Result<_, _>>()?)))
+        },
+        Value::Map(map) => {
+            let mut built = collections::Map::new();
+            for (k, v) in map {
+                let k = recr_qquote(Box::new(k.into_value()), env, depth)?;
+                built.insert(Key::new(*k)?, *recr_qquote(Box::new(v), env, depth)?);
             }
+            Ok(Box::new(Value::Map(built)))
         },
-       v => Ok(Box::new(v.clone()))
+        v => Ok(Box::new(v)),
+    }
+}
+
+// Quasiquotes each item of a list or collection, splicing in the lists that
+// unquote-splicing gives
+fn qquote_items(items: impl IntoIterator<Item = RValue>, env: &mut Env, depth: usize) -> Result<LinkedList<RValue>, Errors> {
+    let mut built = LinkedList::new();
+    for item in items {
+        match quoted_by(&item, &Value::UnquoteSplicing) {
+            Some(form) if depth == 1 => match *EVAL(form, env)? {
+                Value::List(spliced) => built.append(&mut spliced.into_items()),
+                v => return Err(Errors::type_error("list", &v)),
+            },
+            _ => built.push_back(recr_qquote(item, env, depth)?),
+        }
     }
+    Ok(built)
 }
 
 fn eval_quote(lst: LinkedList<RValue>) -> FResult {
@@ -88,33 +286,88 @@ fn eval_quote(lst: LinkedList<RValue>) -> FResult {
     list.pop_front(); // Remove quote;
     match list.front() {
         Some(v) => Ok(v.clone()),
-        None => Err(Errors::FormError),
+        None => Err(Errors::FormError(String::from("quote expects an expression"))),
     }
 }
 
-fn eval_let(list: LinkedList<RValue>, env: &mut Env) -> FResult{
+// `let`, `let*` and `letrec` all bind names in a new scope and then evaluate
+// their body there like `do`. They differ in where the values are evaluated:
+// `let` in the enclosing scope, `let*` after the bindings before them, and
+// `letrec` in the new scope with every name already bound, so local
+// functions can call each other.
+fn eval_let(list: LinkedList<RValue>, env: &mut Env) -> Result<Step, Errors>{
     let mut list = list;
-    list.pop_front(); // Remove 'let'
-    // Extract the binding term
-    let binding = match list.pop_front() {
-        Some(v) => Ok(v),
-        None => Err(Errors::FormError),
-    }?;
-    let mut binding = match binding.deref().clone() {
-        Value::List(lst) => Ok(lst),
-        _ => Err(Errors::FormError),
+    let kind = list.pop_front().unwrap();
+    let form = kind.to_string();
+    let bindings = match list.pop_front() {
+        Some(v) => build_bindings(*v, &form),
+        None => Err(Errors::FormError(format!("{} expects a list of bindings", form))),
     }?;
-    binding.push_front(Box::new(Value::Define)); // Bush define to the front
-    // Create a new scope
-    let mut scope = Env::push((*env).clone());
-    // Evaluate the binding in the new scope
-    eval_define(binding, &mut scope)?;
-    // Evaluate body in the new scope
-    let body = match list.pop_front() {
-        Some(body) => Ok(body),
-        None => Err(Errors::FormError),
+    if list.is_empty() {
+        return Err(Errors::FormError(format!("{} expects a body", form)));
+    }
+
+    let mut scope = Env::push(env.clone());
+    match kind.deref() {
+        Value::Let => {
+            for (name, value) in bindings {
+                let value = EVAL(value, env)?;
+                scope.set(name, *value);
+            }
+        },
+        Value::LetStar => {
+            for (name, value) in bindings {
+                let value = EVAL(value, &mut scope)?;
+                scope.set(name, *value);
+            }
+        },
+        _ => {
+            for (name, _) in &bindings {
+                scope.set(name.clone(), Value::Nil);
+            }
+            for (name, value) in bindings {
+                let value = EVAL(value, &mut scope)?;
+                scope.set(name, *value);
+            }
+        },
+    }
+
+    eval_body(list, &mut scope)
+}
+
+// Bindings are written either as pairs, `((a 1) (b 2))`, or flat, `(a 1 b 2)`
+fn build_bindings(bindings: Value, form: &str) -> Result<Vec<(String, RValue)>, Errors> {
+    let malformed = || Errors::FormError(format!("{} expects bindings of the form ((name value) ...)", form));
+    let bindings = match bindings {
+        Value::List(lst) => Ok(lst.into_items()),
+        _ => Err(malformed()),
     }?;
-    EVAL(body, &mut scope)
+
+    let pairs: Vec<LinkedList<RValue>> = match bindings.front().map(|b| b.deref()) {
+        Some(Value::List(_)) => bindings.into_iter().map(|b| match *b {
+            Value::List(pair) => Ok(pair.into_items()),
+            _ => Err(malformed()),
+        }).collect::<Result<_, _>>()?,
+        _ => {
+            if bindings.len() % 2 != 0 {
+                return Err(malformed());
+            }
+            let mut flat = bindings.into_iter();
+            let mut pairs = Vec::new();
+            while let (Some(name), Some(value)) = (flat.next(), flat.next()) {
+                pairs.push(vec![name, value].into_iter().collect());
+            }
+            pairs
+        },
+    };
+
+    pairs.into_iter().map(|pair| {
+        let mut pair = pair.into_iter();
+        match (pair.next().map(|n| *n), pair.next(), pair.next()) {
+            (Some(Value::Symbol(name)), Some(value), None) => Ok((name, value)),
+            _ => Err(malformed()),
+        }
+    }).collect()
 }
 
 fn eval_defmacro(list: LinkedList<RValue>, env: &mut Env) -> FResult {
@@ -123,18 +376,18 @@ fn eval_defmacro(list: LinkedList<RValue>, env: &mut Env) -> FResult {
     let name = match list.pop_front() {
         Some(v) => match *v {
             Value::Symbol(s) => Ok(s),
-            _ => Err(Errors::TypeError),
+            v => Err(Errors::type_error("symbol", &v)),
         }
-        _ => panic!("Empty list in define()"),
+        None => Err(Errors::FormError(String::from("expected a name and a value"))),
     }?;
     let target = match list.pop_front() {
         Some(b) => Ok(b),
-        None => Err(Errors::FormError),
+        None => Err(Errors::FormError(String::from("expected a name and a value"))),
     }?;
     let target = EVAL(target, env)?;
     let target = match target.deref() {
         Value::DynFunc(df) => Ok(Value::DynFunc(to_macro(df.clone()))),
-        _ => Err(Errors::TypeError),
+        v => Err(Errors::type_error("function", v)),
     }?;
 
     env.set(name, target);
@@ -142,7 +395,41 @@ fn eval_defmacro(list: LinkedList<RValue>, env: &mut Env) -> FResult {
 }
 
 fn to_macro(f: DynamicFunction) -> DynamicFunction {
-    DynamicFunction::new_macro(f.parameters, f.body)
+    DynamicFunction { is_macro: true, ..f }
+}
+
+// (define-syntax name (syntax-rules ...)) binds a macro, which the value
+// must already be
+fn eval_define_syntax(list: LinkedList<RValue>, env: &mut Env) -> FResult {
+    let mut list = list;
+    list.pop_front(); // Remove `define-syntax`
+    let (name, target) = match (list.pop_front().map(|n| *n), list.pop_front(), list.pop_front()) {
+        (Some(Value::Symbol(name)), Some(target), None) => Ok((name, target)),
+        _ => Err(Errors::FormError(String::from("define-syntax expects a name and a macro"))),
+    }?;
+    let target = EVAL(target, env)?;
+    match *target {
+        Value::DynFunc(df) if df.is_macro => {
+            env.set(name.clone(), Value::DynFunc(DynamicFunction { name: Some(name), ..df }));
+            Ok(Box::new(Value::Nil))
+        },
+        v => Err(Errors::type_error("macro", &v)),
+    }
+}
+
+// A syntax-rules macro takes its whole argument list and hands it to a
+// builtin that does the pattern matching
+fn eval_syntax_rules(list: LinkedList<RValue>, env: &mut Env) -> FResult {
+    let rules = syntax::SyntaxRules::parse(list)?;
+    let args = syntax::gensym("args");
+    let expand = Builtin::new("syntax-rules", Arity::Exactly(1), move |p| match p.front().map(|b| b.deref()) {
+        Some(Value::List(args)) => rules.expand(args),
+        _ => Err(Errors::FormError(String::from("syntax-rules expects its arguments as a list"))),
+    });
+    let body = vec![Box::new(Value::Function(expand)), Box::new(Value::Symbol(args.clone()))];
+    let parameters = Parameters { rest: Some(args), ..Parameters::default() };
+    Ok(Box::new(Value::DynFunc(DynamicFunction::new_macro(
+        parameters, Box::new(Value::List(body.into_iter().collect())), env.clone()))))
 }
 
 fn eval_define(list: LinkedList<RValue>, env: &mut Env) -> FResult {
@@ -151,13 +438,13 @@ fn eval_define(list: LinkedList<RValue>, env: &mut Env) -> FResult {
     let name = match list.pop_front() {
         Some(v) => match *v {
             Value::Symbol(s) => Ok(s),
-            _ => Err(Errors::TypeError),
+            v => Err(Errors::type_error("symbol", &v)),
         }
-        _ => panic!("Empty list in define()"),
+        None => Err(Errors::FormError(String::from("expected a name and a value"))),
     }?;
     let target = match list.pop_front() {
         Some(b) => Ok(b),
-        None => Err(Errors::FormError),
+        None => Err(Errors::FormError(String::from("expected a name and a value"))),
     }?;
     let target = EVAL(target, env)?;
 
@@ -165,27 +452,184 @@ fn eval_define(list: LinkedList<RValue>, env: &mut Env) -> FResult {
     Ok(Box::new(Value::Nil))
 }
 
-fn handle_function(lst: LinkedList<RValue>, env: &mut Env) -> FResult {
+// (set! name value) changes an existing binding, in whichever scope it is in
+fn eval_set(list: LinkedList<RValue>, env: &mut Env) -> FResult {
+    let mut list = list;
+    list.pop_front(); // Remove `set!`
+    if list.len() != 2 {
+        return Err(Errors::FormError(String::from("set! expects a name and a value")));
+    }
+    let name = match *list.pop_front().unwrap() {
+        Value::Symbol(s) => Ok(s),
+        v => Err(Errors::type_error("symbol", &v)),
+    }?;
+    let value = EVAL(list.pop_front().unwrap(), env)?;
+    env.assign(name, *value)?;
+    Ok(Box::new(Value::Nil))
+}
+
+// (try body... (catch e handler...) (finally cleanup...)) evaluates body, and
+// if it fails evaluates handler with e bound to what went wrong. Cleanup runs
+// however the rest ends, and its value is ignored. Either clause may be left out.
+fn eval_try(list: LinkedList<RValue>, env: &mut Env) -> FResult {
+    let mut list = list;
+    list.pop_front(); // Remove `try`
+    let clause = |v: &Value, name: &str| match v {
+        Value::List(lst) => matches!(lst.front().map(|b| b.deref()), Some(Value::Symbol(s)) if s == name),
+        _ => false,
+    };
+    let mut finally = None;
+    if list.back().is_some_and(|v| clause(v, "finally")) {
+        finally = list.pop_back();
+    }
+    // The catch clause is checked before anything runs, so a malformed one
+    // can't keep the cleanup from running
+    let mut catch = None;
+    if list.back().is_some_and(|v| clause(v, "catch")) {
+        let mut handler = match *list.pop_back().unwrap() {
+            Value::List(lst) => lst.into_items(),
+            _ => unreachable!(),
+        };
+        handler.pop_front(); // Remove `catch`
+        let name = match handler.pop_front().map(|b| *b) {
+            Some(Value::Symbol(s)) => Ok(s),
+            _ => Err(Errors::FormError(String::from("catch expects a name to bind the error to"))),
+        }?;
+        catch = Some((name, handler));
+    }
+
+    let mut result = eval_forms(list, env);
+    if let (Err(e), Some((name, handler))) = (&result, catch) {
+        let mut scope = Env::push(env.clone());
+        scope.set(name, e.to_value());
+        result = eval_forms(handler, &mut scope);
+    }
+    if let Some(finally) = finally {
+        let mut finally = match *finally {
+            Value::List(lst) => lst.into_items(),
+            _ => unreachable!(),
+        };
+        finally.pop_front(); // Remove `finally`
+        eval_forms(finally, env)?;
+    }
+    result
+}
+
+// Evaluates forms in turn, giving the value of the last
+fn eval_forms(forms: LinkedList<RValue>, env: &mut Env) -> FResult {
+    let mut result = Box::new(Value::Nil);
+    for form in forms {
+        result = EVAL(form, env)?;
+    }
+    Ok(result)
+}
+
+// Calls a function value on arguments that have already been evaluated
+pub fn apply(f: RValue, args: Params) -> FResult {
+    match f.deref() {
+        Value::Function(_) => (),
+        Value::DynFunc(df) if !df.is_macro => (),
+        v => return Err(Errors::NotAFunction(v.to_string())),
+    }
+    let mut call = args;
+    call.push_front(f);
+    match handle_function(call, "<anonymous>")? {
+        Step::Tail(body, mut scope) | Step::Call(body, mut scope, _) =>
+            EVAL(body, &mut scope),
+        Step::Done(v) => Ok(v),
+    }
+}
+
+// `name` is what the function was called as, for error messages
+fn handle_function(lst: LinkedList<RValue>, name: &str) -> Result<Step, Errors> {
     match lst.front() {
         Some(b) => match b.deref() {
-            Value::Function(_) => handle_builtin(lst),
-            Value::DynFunc(_) => handle_dyn_function(lst, env),
-            _ => Err(Errors::FormError),
+            Value::Function(_) => handle_builtin(lst).map(Step::Done),
+            Value::DynFunc(_) => handle_dyn_function(lst, name),
+            v => Err(Errors::NotAFunction(v.to_string())),
         }
-        None => Err(Errors::FormError),
+        None => Err(Errors::FormError(String::from("empty function call"))),
     }
 
 }
 
-fn handle_dyn_function(lst: LinkedList<RValue>, env: &mut Env) -> FResult {
+// Functions that were given a name are reported by it, others by what they
+// were called as
+fn handle_dyn_function(lst: LinkedList<RValue>, name: &str) -> Result<Step, Errors> {
     let mut list = lst;
-    let f = match list.pop_front().unwrap().deref().clone() {
+    let f = match *list.pop_front().unwrap() {
         Value::DynFunc(f) => Ok(f),
-        _ => Err(Errors::FormError),
+        v => Err(Errors::NotAFunction(v.to_string())),
+    }?;
+    let name = f.name.clone().unwrap_or_else(|| String::from(name));
+    let clause = match f.clauses.iter().position(|c| c.parameters.accepts(list.len())) {
+        Some(i) => Ok(i),
+        None => Err(Errors::SignatureError(name.clone(), f.signature(), list.len())),
     }?;
-    let mut scope = Env::push((*env).clone());
-    scope.add_all(f.parameters, list)?;
-    EVAL(f.body, &mut scope)
+    let Clause { parameters, body } = f.clauses[clause].clone();
+    let mut scope = Env::push(f.closure.clone());
+    // A named function can call itself by name; its parameters shadow that
+    if let Some(own_name) = &f.name {
+        scope.set(own_name.clone(), Value::DynFunc(f.clone()));
+    }
+    bind_parameters(&parameters, list, &mut scope, &name)?;
+    Ok(Step::Call(body, scope, name))
+}
+
+// Binds a call's arguments to the parameters they belong to in `scope`
+fn bind_parameters(params: &Parameters, args: Params, scope: &mut Env, name: &str) -> Result<(), Errors> {
+    let count = args.len();
+    let arity_error = || Errors::SignatureError(String::from(name), params.to_string(), count);
+    let positional = params.required.len() + params.optional.len();
+    let variadic = params.rest.is_some() || !params.keys.is_empty();
+    if args.len() < params.required.len() || (args.len() > positional && !variadic) {
+        return Err(arity_error());
+    }
+
+    let mut remaining = args;
+    for param in &params.required {
+        scope.set(param.clone(), *remaining.pop_front().unwrap());
+    }
+    for (param, default) in &params.optional {
+        let value = match (remaining.pop_front(), default) {
+            (Some(v), _) => v,
+            (None, Some(default)) => EVAL(default.clone(), scope)?,
+            (None, None) => Box::new(Value::Nil),
+        };
+        scope.set(param.clone(), *value);
+    }
+    if params.keys.is_empty() {
+        if let Some(rest) = &params.rest {
+            scope.set(rest.clone(), Value::List(remaining.into()));
+        }
+        return Ok(());
+    }
+    // The rest parameter sees the keyword arguments too
+    if let Some(rest) = &params.rest {
+        scope.set(rest.clone(), Value::List(remaining.clone().into()));
+    }
+
+    if !remaining.len().is_multiple_of(2) {
+        return Err(arity_error());
+    }
+    let mut given = Vec::new();
+    while let (Some(key), Some(value)) = (remaining.pop_front(), remaining.pop_front()) {
+        match key.deref() {
+            Value::Symbol(k) if k.starts_with(':')
+                && params.keys.iter().any(|(param, _)| param == &k[1..]) =>
+                given.push((String::from(&k[1..]), value)),
+            k => return Err(Errors::FormError(format!("{} has no keyword parameter {}", name, k))),
+        }
+    }
+    for (param, default) in &params.keys {
+        let value = match (given.iter().position(|(k, _)| k == param), default) {
+            (Some(i), _) => given.remove(i).1,
+            (None, Some(default)) => EVAL(default.clone(), scope)?,
+            (None, None) => Box::new(Value::Nil),
+        };
+        scope.set(param.clone(), *value);
+    }
+    Ok(())
 }
 
 fn handle_builtin(lst: LinkedList<RValue>) -> FResult {
@@ -198,15 +642,37 @@ fn handle_builtin(lst: LinkedList<RValue>) -> FResult {
 
     let f = match value {
         Value::Function(f) => Ok(f),
-        _ => Err(Errors::NotAFunction),
+        v => Err(Errors::NotAFunction(v.to_string())),
     }?;
 
-    f(list)
+    f.call(list)
 }
 
 fn eval_ast(input:Value, env: &mut Env) -> FResult{
     match input {
+        // Keywords, like `:name`, evaluate to themselves
+        Value::Symbol(s) if s.starts_with(':') => Ok(Box::new(Value::Symbol(s))),
         Value::Symbol(s) => env.lookup(s),
+        // Collection literals evaluate their contents
+        Value::Vector(vector) => {
+            let vector = vector.into_iter().map(|v| EVAL(Box::new(v), env).map(|v| *v))
+                .collect::<Result<_, _>>()?;
+            Ok(Box::new(Value::Vector(vector)))
+        },
+        Value::Map(map) => {
+            let mut evald = collections::Map::new();
+            for (k, v) in map {
+                let k = EVAL(Box::new(k.into_value()), env)?;
+                let v = EVAL(Box::new(v), env)?;
+                evald.insert(Key::new(*k)?, *v);
+            }
+            Ok(Box::new(Value::Map(evald)))
+        },
+        Value::Set(set) => {
+            let set = set.into_iter().map(|k| Key::new(*EVAL(Box::new(k.into_value()), env)?))
+                .collect::<Result<_, _>>()?;
+            Ok(Box::new(Value::Set(set)))
+        },
         other => Ok(Box::new(other))
     }
 }
@@ -221,21 +687,24 @@ fn recr_eval_list(lst: LinkedList<RValue>, env: &mut Env)
     Ok(new)
 }
 
-fn eval_do(list: LinkedList<RValue>, env: &mut Env) -> FResult {
+fn eval_do(list: LinkedList<RValue>, env: &mut Env) -> Result<Step, Errors> {
     let mut list = list;
     list.pop_front(); // remove `do`
-    let mut result = Ok(Box::new(Value::Nil));
+    let last = match list.pop_back() {
+        Some(last) => last,
+        None => return Ok(Step::Done(Box::new(Value::Nil))),
+    };
     for expr in list {
-        result = EVAL(expr, env);
+        EVAL(expr, env)?;
     }
-    result
+    Ok(Step::Tail(last, env.clone()))
 }
     
-fn eval_if(list: LinkedList<RValue>, env: &mut Env) -> FResult {
+fn eval_if(list: LinkedList<RValue>, env: &mut Env) -> Result<Step, Errors> {
     let mut list = list;
     list.pop_front(); // remove `if`
     if list.len() < 2 {
-        return Err(Errors::FormError);
+        return Err(Errors::FormError(String::from("if expects a condition and a branch")));
     }
 
     let cond = list.pop_front().unwrap();
@@ -248,50 +717,241 @@ fn eval_if(list: LinkedList<RValue>, env: &mut Env) -> FResult {
     let cond = EVAL(cond, env)?;
 
     let result =
-        if *cond == Value::True {
+        if cond.is_truthy() {
             a
         } else {
             b
         };
 
-   EVAL(result, env) 
-    
+    Ok(Step::Tail(result, env.clone()))
+}
+
+// Evaluates forms in turn like `do`, the last in tail position
+fn eval_body(body: LinkedList<RValue>, env: &mut Env) -> Result<Step, Errors> {
+    let mut body = body;
+    body.push_front(Box::new(Value::Do));
+    eval_do(body, env)
+}
+
+// `and` stops at the first false value and `or` at the first true one,
+// giving back that value, or the last if none stops them
+fn eval_and_or(list: LinkedList<RValue>, env: &mut Env) -> Result<Step, Errors> {
+    let mut list = list;
+    let stop_on = matches!(*list.pop_front().unwrap(), Value::Or);
+    let last = match list.pop_back() {
+        Some(last) => last,
+        None if stop_on => return Ok(Step::Done(Box::new(Value::Nil))),
+        None => return Ok(Step::Done(Box::new(Value::True))),
+    };
+    for expr in list {
+        let value = EVAL(expr, env)?;
+        if value.is_truthy() == stop_on {
+            return Ok(Step::Done(value));
+        }
+    }
+    Ok(Step::Tail(last, env.clone()))
+}
+
+fn is_else(v: &Value) -> bool {
+    matches!(v, Value::Symbol(s) if s == "else")
+}
+
+// (cond (test body...) ... (else body...)) runs the body of the first clause
+// whose test is true. A clause without a body gives the test's value.
+fn eval_cond(list: LinkedList<RValue>, env: &mut Env) -> Result<Step, Errors> {
+    let mut list = list;
+    list.pop_front(); // Drop `cond`
+    for clause in list {
+        let mut clause = match *clause {
+            Value::List(clause) if !clause.is_empty() => Ok(clause.into_items()),
+            _ => Err(Errors::FormError(String::from("cond expects clauses of the form (test body...)"))),
+        }?;
+        let test = clause.pop_front().unwrap();
+        if is_else(&test) {
+            return eval_body(clause, env);
+        }
+        let value = EVAL(test, env)?;
+        if value.is_truthy() {
+            if clause.is_empty() {
+                return Ok(Step::Done(value));
+            }
+            return eval_body(clause, env);
+        }
+    }
+    Ok(Step::Done(Box::new(Value::Nil)))
+}
+
+// (case key ((datum...) body...) (datum body...) ... (else body...)) runs the
+// body of the first clause listing a datum equal to key. Datums aren't evaluated.
+fn eval_case(list: LinkedList<RValue>, env: &mut Env) -> Result<Step, Errors> {
+    let mut list = list;
+    list.pop_front(); // Drop `case`
+    let key = match list.pop_front() {
+        Some(key) => EVAL(key, env),
+        None => Err(Errors::FormError(String::from("case expects a key and clauses"))),
+    }?;
+    for clause in list {
+        let mut clause = match *clause {
+            Value::List(clause) if !clause.is_empty() => Ok(clause.into_items()),
+            _ => Err(Errors::FormError(String::from("case expects clauses of the form ((datum...) body...)"))),
+        }?;
+        let data = clause.pop_front().unwrap();
+        let matched = match data.deref() {
+            v if is_else(v) => true,
+            Value::List(data) => data.iter().any(|d| same_datum(&key, d)),
+            datum => same_datum(&key, datum),
+        };
+        if matched {
+            return eval_body(clause, env);
+        }
+    }
+    Ok(Step::Done(Box::new(Value::Nil)))
 }
 
-fn eval_fn(list: LinkedList<RValue>) -> FResult {
+fn same_datum(a: &Value, b: &Value) -> bool {
+    a == b || Value::equals(a, b).unwrap_or(false)
+}
+
+// (when test body...) runs the body if test is true, `unless` if it is false
+fn eval_when(list: LinkedList<RValue>, env: &mut Env) -> Result<Step, Errors> {
+    let mut list = list;
+    let wanted = matches!(*list.pop_front().unwrap(), Value::When);
+    let test = match list.pop_front() {
+        Some(test) => EVAL(test, env),
+        None => Err(Errors::FormError(String::from("when and unless expect a test and a body"))),
+    }?;
+    if test.is_truthy() == wanted {
+        eval_body(list, env)
+    } else {
+        Ok(Step::Done(Box::new(Value::Nil)))
+    }
+}
+
+// (fn name? (params) body...) or (fn name? ((params) body...) ...)
+fn eval_fn(list: LinkedList<RValue>, span: Option<Span>, env: &mut Env) -> FResult {
     let mut list = list;
     list.pop_front(); // Drop `fn`
+    let name = match list.front().map(|b| b.deref()) {
+        Some(Value::Symbol(s)) => Some(s.clone()),
+        _ => None,
+    };
+    if name.is_some() {
+        list.pop_front();
+    }
+    let clauses = build_clauses(list)?;
+    Ok(Box::new(Value::DynFunc(make_function(name, None, clauses, span, env))))
+}
+
+// (defn name "docstring"? (params) body...), with the same clauses as `fn`
+fn eval_defn(list: LinkedList<RValue>, span: Option<Span>, env: &mut Env) -> FResult {
+    let mut list = list;
+    list.pop_front(); // Drop `defn`
+    let name = match list.pop_front().map(|b| *b) {
+        Some(Value::Symbol(s)) => Ok(s),
+        Some(v) => Err(Errors::type_error("symbol", &v)),
+        None => Err(Errors::FormError(String::from("defn expects a name, a parameter list and a body"))),
+    }?;
+    let doc = match list.front().map(|b| b.deref()) {
+        Some(Value::Str(s)) if list.len() > 1 => Some(s.clone()),
+        _ => None,
+    };
+    if doc.is_some() {
+        list.pop_front();
+    }
+    let clauses = build_clauses(list)?;
+    let f = make_function(Some(name.clone()), doc, clauses, span, env);
+    env.set(name, Value::DynFunc(f));
+    Ok(Box::new(Value::Nil))
+}
+
+// A named function is bound to its name in each call, see `handle_dyn_function`
+fn make_function(name: Option<String>, doc: Option<String>, clauses: Vec<Clause>,
+                 source: Option<Span>, env: &Env) -> DynamicFunction {
+    DynamicFunction { clauses, closure: env.clone(), is_macro: false, name, doc, source }
+}
+
+// Either a single `(params) body...` or several `((params) body...)`
+fn build_clauses(list: LinkedList<RValue>) -> Result<Vec<Clause>, Errors> {
+    let multiple = match list.front().map(|b| b.deref()) {
+        Some(Value::List(first)) => matches!(first.front().map(|b| b.deref()), Some(Value::List(_))),
+        _ => false,
+    };
+    if !multiple {
+        return Ok(vec![build_clause(list)?]);
+    }
+    list.into_iter().map(|clause| match *clause {
+        Value::List(clause) => build_clause(clause.into_items()),
+        v => Err(Errors::FormError(format!("Expected a clause ((params) body...), recieved {}", v))),
+    }).collect()
+}
+
+// Several body forms are evaluated in turn, like `do`
+fn build_clause(list: LinkedList<RValue>) -> Result<Clause, Errors> {
+    let mut list = list;
     if list.len() < 2 {
-        return Err(Errors::FormError);
+        return Err(Errors::FormError(String::from("fn expects a parameter list and a body")));
     }
-    let params = build_param_list(list.pop_front().unwrap())?;
+    let parameters = build_param_list(*list.pop_front().unwrap())?;
+    let body = if list.len() == 1 {
+        list.pop_front().unwrap()
+    } else {
+        list.push_front(Box::new(Value::Do));
+        Box::new(Value::List(list.into()))
+    };
+    Ok(Clause { parameters, body })
+}
 
-    let body = list.pop_front().unwrap();
-    let df = DynamicFunction::new(params, body);
-    Ok(Box::new(Value::DynFunc(df)))
+// Which part of a parameter list is being read
+enum Section {
+    Required,
+    Optional,
+    Rest,
+    Key,
 }
 
-fn build_param_list(list: RValue) -> Result<LinkedList<String>,Errors> {
-    match list.deref() {
-        Value::List(list) => 
-            Ok(list.iter()
-            .map(|v| match v.deref() {
-                Value::Symbol(s) => Some(s),
-                _ => None, })
-            .filter(|o| match o {
-                Some(_) => true,
-                None => false })
-            .map(|o| match o {
-                Some(s) => (*s).clone(),
-                None => panic!(""),
-            }).collect()),
-            
-            
-        _ => Err(Errors::FormError),
+fn build_param_list(list: Value) -> Result<Parameters, Errors> {
+    let list = match list {
+        Value::List(list) => Ok(list),
+        _ => Err(Errors::FormError(String::from("fn expects a parameter list"))),
+    }?;
+    let invalid = |v: &Value| Errors::FormError(format!("Invalid parameter {}", v));
+
+    let mut params = Parameters::default();
+    let mut section = Section::Required;
+    for param in list {
+        match (*param, &section) {
+            (Value::Symbol(s), _) if s == "&optional" => section = Section::Optional,
+            (Value::Symbol(s), _) if s == "&" => section = Section::Rest,
+            (Value::Symbol(s), _) if s == "&key" => section = Section::Key,
+            (Value::Symbol(s), Section::Required) => params.required.push(s),
+            (Value::Symbol(s), Section::Rest) if params.rest.is_none() => params.rest = Some(s),
+            (param, Section::Optional) => params.optional.push(build_defaulted(param)?),
+            (param, Section::Key) => params.keys.push(build_defaulted(param)?),
+            (v, _) => return Err(invalid(&v)),
+        }
+    }
+    if matches!(section, Section::Rest) && params.rest.is_none() {
+        return Err(Errors::FormError(String::from("& must be followed by a parameter name")));
+    }
+    Ok(params)
+}
+
+// An optional or keyword parameter, `name` or `(name default)`
+fn build_defaulted(param: Value) -> Result<(String, Option<RValue>), Errors> {
+    match param {
+        Value::Symbol(s) => Ok((s, None)),
+        Value::List(lst) if lst.len() == 2 => {
+            let mut lst = lst.into_items();
+            match *lst.pop_front().unwrap() {
+                Value::Symbol(s) => Ok((s, lst.pop_front())),
+                v => Err(Errors::FormError(format!("Invalid parameter {}", v))),
+            }
+        },
+        v => Err(Errors::FormError(format!("Invalid parameter {}", v))),
     }
 }
 
-fn eval_eval(list: LinkedList<RValue>, env: &mut Env) -> FResult {
+fn eval_eval(list: LinkedList<RValue>, env: &mut Env) -> Result<Step, Errors> {
     let mut list = list;
     list.pop_front(); //Drop `eval`
     let target = match list.pop_front() {
@@ -299,5 +959,5 @@ fn eval_eval(list: LinkedList<RValue>, env: &mut Env) -> FResult {
         None => Err(Errors::ArityError(0, 1)),
     }?;
     let evald = EVAL(target, env)?;
-    EVAL(evald, env)
+    Ok(Step::Tail(evald, env.clone()))
 }
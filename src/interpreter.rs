@@ -0,0 +1,89 @@
+use crate::*;
+use crate::builtins::{build_init_env, load_file, load_source};
+use crate::evals::apply;
+use crate::parsing::read_all;
+use crate::stdlib::get_std_lib;
+use std::path::Path;
+
+// An RLisp session: the global environment with the builtins and standard
+// library loaded, plus entry points for reading and evaluating code from Rust.
+pub struct Interpreter {
+    env: Env,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        let mut interpreter = Interpreter { env: build_init_env() };
+        for f in get_std_lib() {
+            if let Err(e) = interpreter.eval_str(&f) {
+                panic!("Error loading standard library!\n{}", e);
+            }
+        }
+        interpreter
+    }
+
+    // Evaluates each form in `input` in turn, returning the value of the last
+    pub fn eval_str(&mut self, input: &str) -> Result<Value, Errors> {
+        let mut result = Box::new(Value::Nil);
+        for form in read_all(Source::new(input, None))? {
+            result = EVAL(form, &mut self.env)?;
+        }
+        Ok(*result)
+    }
+
+    pub fn eval_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Value, Errors> {
+        Ok(*load_file(&path.as_ref().display().to_string(), &mut self.env)?)
+    }
+
+    // Like `eval_file`, for a program that didn't come from a file. `name`
+    // stands in for the file name in errors.
+    pub fn eval_program(&mut self, text: &str, name: &str) -> Result<Value, Errors> {
+        Ok(*load_source(text, name, &mut self.env)?)
+    }
+
+    // Binds `name` in the global environment
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.env.set(String::from(name), value);
+    }
+
+    // Binds `name` to a native function, see `Env::register`
+    pub fn register<F>(&mut self, name: &str, arity: Arity, f: F)
+        where F: Fn(Params) -> FResult + 'static {
+        self.env.register(name, arity, f);
+    }
+
+    // Binds `name` to an ordinary Rust function, see `Env::register_fn`
+    pub fn register_fn<Args, F: IntoBuiltin<Args>>(&mut self, name: &str, f: F) {
+        self.env.register_fn(name, f);
+    }
+
+    pub fn lookup(&self, name: &str) -> Result<Value, Errors> {
+        Ok(*self.env.lookup(String::from(name))?)
+    }
+
+    // Calls the function bound to `name` with already evaluated arguments
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, Errors> {
+        let f = self.env.lookup(String::from(name))?;
+        let args = args.into_iter().map(Box::new).collect();
+        Ok(*apply(f, args)?)
+    }
+
+    pub fn env(&mut self) -> &mut Env {
+        &mut self.env
+    }
+}
+
+// Closures kept in the global scope can refer back to it through the scopes
+// they were made in, so it's emptied to free them. Values taken out of the
+// interpreter no longer see its definitions once it's gone.
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        self.env.clear();
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
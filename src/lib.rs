@@ -1,26 +1,129 @@
+use num_bigint::BigInt;
+use num_rational::BigRational;
 use std::collections::LinkedList;
 use std::collections::HashMap;
+use std::cell::RefCell;
 use std::error::Error;
 use std::fmt::Display;
+use std::rc::{Rc, Weak};
+use crate::numbers::Number;
+
+#[macro_use]
+extern crate nom;
+
+mod builtins;
+mod collections;
+pub mod convert;
+mod evals;
+mod interpreter;
+mod list;
+pub mod numbers;
+mod parsing;
+mod source;
+mod stdlib;
+mod syntax;
+
+pub use crate::convert::{ToLisp, FromLisp, IntoBuiltin};
+pub use crate::evals::EVAL;
+pub use crate::interpreter::Interpreter;
+pub use crate::list::List;
+pub use crate::collections::Key;
+pub use crate::source::{Source, Span};
+pub use crate::parsing::{READ, read_partial, Partial};
 
 pub type RValue = Box<Value>;
 pub type Params = LinkedList<RValue>;
 pub type FResult = Result<RValue, Errors>;
 
+type Bindings = RefCell<HashMap<String, Value>>;
+
+// Scopes are shared, not copied: cloning an `Env` yields a handle to the
+// same frames, so closures see definitions made after they were created.
 #[derive(Clone)]
 pub struct Env {
-    contents: HashMap<String, Value>,
-    past: Option<Box<Env>>,
+    contents: Contents,
+    past: Option<Rc<Env>>,
+}
+
+// A function stored in the frame it closes over would keep that frame alive
+// through itself forever. The stored copy holds the frame weakly instead, and
+// looking it up hands back a copy that holds it strongly again. That frees
+// the scopes of calls that define local functions. A function stored in an
+// enclosing scope, such as a closure returned to the top level, still forms a
+// cycle through the global scope, which `Interpreter` breaks when dropped.
+#[derive(Clone)]
+enum Contents {
+    Strong (Rc<Bindings>),
+    Weak (Weak<Bindings>),
+}
+
+impl Default for Env {
+    fn default() -> Env {
+        Env { contents: Contents::Strong(Rc::default()), past: None }
+    }
 }
 
 impl Env {
     pub fn new() -> Env{
-        Env { contents: HashMap::new(), past: None }
+        Env::default()
+    }
+
+    fn frame(&self) -> Rc<Bindings> {
+        match &self.contents {
+            Contents::Strong(frame) => frame.clone(),
+            // Only copies stored in the frame itself are weak, and those are
+            // made strong again on the way out
+            Contents::Weak(frame) => frame.upgrade().expect("closure outlived its scope"),
+        }
+    }
+
+    fn frame_ptr(&self) -> *const Bindings {
+        match &self.contents {
+            Contents::Strong(frame) => Rc::as_ptr(frame),
+            Contents::Weak(frame) => frame.as_ptr(),
+        }
+    }
+
+    // A handle to this scope that doesn't keep it alive, for builtins stored
+    // in the scope they need to use
+    pub(crate) fn downgrade(&self) -> Env {
+        match &self.contents {
+            Contents::Strong(frame) => Env { contents: Contents::Weak(Rc::downgrade(frame)), past: self.past.clone() },
+            Contents::Weak(_) => self.clone(),
+        }
+    }
+
+    // A handle that keeps the scope alive again, if it still is
+    pub(crate) fn upgrade(&self) -> Option<Env> {
+        match &self.contents {
+            Contents::Strong(_) => Some(self.clone()),
+            Contents::Weak(frame) => frame.upgrade().map(|frame| Env { contents: Contents::Strong(frame), past: self.past.clone() }),
+        }
+    }
+
+    // The copy of `v` to store in this frame
+    fn detach(&self, v: Value) -> Value {
+        match v {
+            Value::DynFunc(f) if f.closure.frame_ptr() == self.frame_ptr() => {
+                let closure = f.closure.downgrade();
+                Value::DynFunc(DynamicFunction { closure, ..f })
+            },
+            v => v,
+        }
+    }
+
+    // The copy of `v`, stored in this frame, to hand out
+    fn attach(&self, v: &Value) -> Value {
+        match v {
+            Value::DynFunc(f) if matches!(f.closure.contents, Contents::Weak(_)) =>
+                Value::DynFunc(DynamicFunction { closure: self.clone(), ..f.clone() }),
+            v => v.clone(),
+        }
     }
 
     pub fn lookup(&self, symbol: String) -> FResult{
-        match self.contents.get(&symbol) {
-            Some(v) => Ok(Box::new((*v).clone())),
+        match self.frame().borrow().get(&symbol) {
+            Some(v) => Ok(Box::new(self.attach(v))),
             None => match &self.past {
                 Some(scope) => scope.lookup(symbol),
                 None => Err(Errors::SymbolNotFound(symbol.clone()))
@@ -29,12 +132,54 @@ impl Env {
     }
 
     pub fn set(&mut self, symbol: String, v: Value) {
-        self.contents.insert(symbol, v);
+        let v = self.detach(v);
+        self.frame().borrow_mut().insert(symbol, v);
+    }
+
+    // Rebinds the innermost existing binding of `symbol`, wherever it is
+    pub fn assign(&self, symbol: String, v: Value) -> Result<(), Errors> {
+        if let Some(existing) = self.frame().borrow_mut().get_mut(&symbol) {
+            *existing = self.detach(v);
+            return Ok(());
+        }
+        match &self.past {
+            Some(scope) => scope.assign(symbol, v),
+            None => Err(Errors::SymbolNotFound(symbol)),
+        }
+    }
+
+    // Binds `name` to a native function, which may be a closure over host state
+    pub fn register<F>(&mut self, name: &str, arity: Arity, f: F)
+        where F: Fn(Params) -> FResult + 'static {
+        self.set(String::from(name), Value::Function(Builtin::new(name, arity, f)));
+    }
+
+    // Binds `name` to an ordinary Rust function, see `IntoBuiltin`
+    pub fn register_fn<Args, F: IntoBuiltin<Args>>(&mut self, name: &str, f: F) {
+        self.set(String::from(name), Value::Function(f.into_builtin(name)));
+    }
+
+    // Every name bound here or in an enclosing scope, sorted
+    pub fn symbols(&self) -> Vec<String> {
+        let mut symbols: Vec<String> = self.frame().borrow().keys().cloned().collect();
+        if let Some(past) = &self.past {
+            symbols.extend(past.symbols());
+        }
+        symbols.sort();
+        symbols.dedup();
+        symbols
+    }
+
+    // Drops every binding in this scope
+    pub(crate) fn clear(&self) {
+        // Taken out first, so nothing dropped runs while the frame is borrowed
+        let bindings = std::mem::take(&mut *self.frame().borrow_mut());
+        drop(bindings);
     }
 
     pub fn push(old: Env) -> Env {
         let mut n = Env::new();
-        n.past = Some(Box::new(old));
+        n.past = Some(Rc::new(old));
         n
     }
 
@@ -46,18 +191,31 @@ impl Env {
         for (symbol, value) in ss.iter().zip(vs) {
             self.set(symbol.to_string(), (*value).clone());
         }
-        return Ok(());
+        Ok(())
     }
 
     pub fn unwrap(&self) -> Value {
         let mut r = LinkedList::new();
-        for (symbol,value) in &self.contents {
+        for (symbol,value) in self.frame().borrow().iter() {
             let mut this = LinkedList::new();
             this.push_back(Box::new(Value::Symbol(symbol.to_string())));
-            this.push_back(Box::new(value.clone()));
-            r.push_back(Box::new(Value::List(this)))
+            this.push_back(Box::new(self.attach(value)));
+            r.push_back(Box::new(Value::List(this.into())))
         }
-        Value::List(r)
+        Value::List(r.into())
+    }
+}
+
+impl std::fmt::Debug for Env {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        // Environments routinely contain closures that point back at them
+        write!(fmt, "<Env>")
+    }
+}
+
+impl PartialEq for Env {
+    fn eq(&self, other: &Env) -> bool {
+        self.frame_ptr() == other.frame_ptr()
     }
 }
 
@@ -65,26 +223,116 @@ impl Env {
 
 #[derive(Debug)]
 pub enum Errors {
-    TypeError,
+    // got, expected
+    TypeError (String, String),
     ParseError (String),
     SymbolNotFound (String),
-    NotAFunction,
-    FormError,
+    // The value that was called
+    NotAFunction (String),
+    // What was wrong with the form
+    FormError (String),
     // got, expected
     ArityError (i32, i32),
+    // A call that doesn't fit a function's parameters: the function, its
+    // parameter list, and the number of arguments given
+    SignatureError (String, String, usize),
     IOError (String),
+    ArithmeticError (String),
+    // An index, and the length of what it was used on
+    IndexError (i64, usize),
+    // Raised by a native function registered by the host
+    NativeError (String),
+    // Another error, with where it happened attached by the evaluator
+    Traced (Box<Errors>, Trace),
+    // Another error, raised by the nth top-level form of a file being loaded
+    Loading (Box<Errors>, String, usize),
+    // A value passed to `throw`
+    Thrown (Box<Value>),
+}
+
+impl Errors {
+    pub fn type_error(expected: &str, got: &Value) -> Errors {
+        Errors::TypeError(String::from(got.type_name()), String::from(expected))
+    }
+
+    // The error itself, without any trace information
+    pub fn root(&self) -> &Errors {
+        match self {
+            Errors::Traced(e, _) | Errors::Loading(e, _, _) => e.root(),
+            e => e,
+        }
+    }
+
+    // What `catch` binds: the value thrown, or an error object describing
+    // any other error
+    pub fn to_value(&self) -> Value {
+        let e = self.root();
+        let (kind, irritants) = match e {
+            Errors::Thrown(v) => return (**v).clone(),
+            Errors::TypeError(got, expected) =>
+                ("type-error", vec![Value::Str(got.clone()), Value::Str(expected.clone())]),
+            Errors::ParseError(_) => ("parse-error", vec![]),
+            Errors::SymbolNotFound(s) => ("unbound-symbol", vec![Value::Symbol(s.clone())]),
+            Errors::NotAFunction(s) => ("not-a-function", vec![Value::Str(s.clone())]),
+            Errors::FormError(_) => ("form-error", vec![]),
+            Errors::ArityError(got, expected) =>
+                ("arity-error", vec![Value::Int(*got as i64), Value::Int(*expected as i64)]),
+            Errors::SignatureError(name, params, got) =>
+                ("arity-error", vec![Value::Str(name.clone()), Value::Str(params.clone()), Value::Int(*got as i64)]),
+            Errors::IOError(_) => ("io-error", vec![]),
+            Errors::ArithmeticError(_) => ("arithmetic-error", vec![]),
+            Errors::IndexError(index, len) =>
+                ("index-error", vec![Value::Int(*index), Value::Int(*len as i64)]),
+            _ => ("error", vec![]),
+        };
+        Value::Error(Box::new(ErrorValue {
+            kind: String::from(kind),
+            message: e.to_string(),
+            irritants,
+        }))
+    }
+}
+
+// An error as a Lisp value. `irritants` are the values the error is about.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorValue {
+    pub kind: String,
+    pub message: String,
+    pub irritants: Vec<Value>,
+}
+
+impl Display for ErrorValue {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{}", self.message)?;
+        for (i, irritant) in self.irritants.iter().enumerate() {
+            write!(fmt, "{}{}", if i == 0 { ": " } else { ", " }, irritant)?;
+        }
+        Ok(())
+    }
 }
 
 impl Display for Errors{
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         let str = match self {
-            Errors::TypeError => String::from("Type Error"),
+            Errors::TypeError (got, exp) => format!("Type Error: Expected {}, recieved {}", exp, got),
             Errors::ParseError(s) => format!("Parse Error: {}", s),
             Errors::SymbolNotFound(s) => format!("Symbol {} is undefined", s),
-            Errors::NotAFunction => String::from("Expected function"),
-            Errors::FormError => String::from("Form Error"),
+            Errors::NotAFunction (s) => format!("Expected function, recieved {}", s),
+            Errors::FormError (s) => format!("Form Error: {}", s),
             Errors::ArityError (got, exp) => format!("Arity Error: Expected {}, recieved {}", exp, got),
+            Errors::SignatureError (name, params, got) =>
+                format!("Arity Error: {} expects {}, recieved {} arguments", name, params, got),
             Errors::IOError (s) => format!("IO Error: {}", s),
+            Errors::ArithmeticError (s) => format!("Arithmetic Error: {}", s),
+            Errors::IndexError (index, len) =>
+                format!("Index Error: index {} is out of bounds for length {}", index, len),
+            Errors::NativeError (s) => s.clone(),
+            Errors::Traced (e, trace) => format!("{}{}", e, trace),
+            Errors::Loading (e, file, form) => format!("{}\n  while loading form {} of {}", e, form, file),
+            Errors::Thrown (v) => match &**v {
+                Value::Error(e) => e.to_string(),
+                v => format!("Uncaught exception: {}", v),
+            },
         };
         write!(fmt, "{}",str)?;
         Ok(())
@@ -92,31 +340,112 @@ impl Display for Errors{
 
 }
 
+// A function call that was in progress when an error happened
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub name: String,
+    pub call_site: Option<Span>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Trace {
+    // The innermost form from source code that was being evaluated
+    pub span: Option<Span>,
+    // Innermost call first
+    pub backtrace: Vec<Frame>,
+}
+
+// Deep non-tail recursion would otherwise print thousands of frames
+const BACKTRACE_LIMIT: usize = 20;
+
+impl Display for Trace {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        if let Some(span) = &self.span {
+            write!(fmt, "\n  in {}\n  at {}", span.text(), span)?;
+        }
+        if !self.backtrace.is_empty() {
+            write!(fmt, "\nBacktrace:")?;
+        }
+        for frame in self.backtrace.iter().take(BACKTRACE_LIMIT) {
+            match &frame.call_site {
+                Some(span) => write!(fmt, "\n  {} called at {}", frame.name, span)?,
+                None => write!(fmt, "\n  {}", frame.name)?,
+            }
+        }
+        if self.backtrace.len() > BACKTRACE_LIMIT {
+            write!(fmt, "\n  ... {} more", self.backtrace.len() - BACKTRACE_LIMIT)?;
+        }
+        Ok(())
+    }
+}
+
 impl Error for Errors{
 
 }
 
+impl From<String> for Errors {
+    fn from(s: String) -> Errors {
+        Errors::NativeError(s)
+    }
+}
+
+impl From<&str> for Errors {
+    fn from(s: &str) -> Errors {
+        Errors::NativeError(String::from(s))
+    }
+}
+
+impl From<std::io::Error> for Errors {
+    fn from(e: std::io::Error) -> Errors {
+        Errors::IOError(e.to_string())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value {
-    Int (i32),
+    Int (i64),
+    BigInt (BigInt),
+    Rational (BigRational),
+    Float (f64),
     Str (String),
     Symbol (String),
-    List (LinkedList<Box<Value>>),
+    List (List),
+    Vector (collections::Vector),
+    Map (collections::Map),
+    Set (collections::Set),
     True,
     False,
     Nil,
     Define,
     Defmacro,
+    Macroexpand,
+    Macroexpand1,
     Let,
+    LetStar,
+    Letrec,
     Do,
     If,
     Fn,
+    Defn,
+    And,
+    Or,
+    Cond,
+    Case,
+    When,
+    Unless,
     Eval,
     Quote,
     Quasiquote,
     Unquote,
-    Function (fn(Params) -> FResult),
+    UnquoteSplicing,
+    SetBang,
+    Try,
+    DefineSyntax,
+    SyntaxRules,
+    Function (Builtin),
     DynFunc (DynamicFunction),
+    Atom (Atom),
+    Error (Box<ErrorValue>),
     Env,
 }
 
@@ -124,7 +453,14 @@ impl Value {
     pub fn equals(a: &Value, b: &Value) -> Result<bool, Errors> {
         match (a, b) {
             (Value::Int(a), Value::Int(b)) => Ok(a == b),
-            (Value::Str(a), Value::Str(b)) => Ok(a == b),
+            (a, b) if a.is_number() && b.is_number() => {
+                let ordering = numbers::compare(&Number::from_value(a)?,
+                                                &Number::from_value(b)?);
+                Ok(ordering == Some(std::cmp::Ordering::Equal))
+            },
+            (Value::Str(a), Value::Str(b)) | (Value::Symbol(a), Value::Symbol(b)) => Ok(a == b),
+            (Value::Nil, Value::Nil) => Ok(true),
+            (Value::Atom(a), Value::Atom(b)) => Ok(a == b),
             (Value::True, Value::True) => Ok(true),
             (Value::False, Value::False) => Ok(true),
             (Value::True, Value::False) => Ok(false),
@@ -132,87 +468,354 @@ impl Value {
             (Value::List(a), Value::List(b)) => {
                 if a.len() == b.len() {
                     a.iter().zip(b)
-                        .fold(Ok(true),
-                              |rest, (a,b)|
-                              match rest {
-                                  Err(e) => Err(e),
-                                  Ok(r) => Ok(r && Value::equals(a, b)?),
-                              })
+                        .try_fold(true,
+                                  |rest, (a,b)| Ok(rest && Value::equals(a, b)?))
+                } else {
+                    Ok(false)
+                }
+            }
+            (Value::Vector(a), Value::Vector(b)) => {
+                if a.len() == b.len() {
+                    a.iter().zip(b)
+                        .try_fold(true,
+                                  |rest, (a,b)| Ok(rest && Value::equals(a, b)?))
+                } else {
+                    Ok(false)
+                }
+            }
+            (Value::Map(a), Value::Map(b)) => {
+                if a.len() == b.len() {
+                    a.iter().try_fold(true, |rest, (k, v)| Ok(rest && match b.get(k) {
+                        Some(w) => Value::equals(v, w)?,
+                        None => false,
+                    }))
                 } else {
                     Ok(false)
                 }
             }
-            _ => Err(Errors::TypeError),
+            (Value::Set(a), Value::Set(b)) => Ok(a == b),
+            // Builtins are equal only to themselves, and functions and macros
+            // only to copies of themselves. Values of different types are
+            // never equal.
+            (a, b) => Ok(a == b),
         }
     }
 
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) | Value::BigInt(_) => "integer",
+            Value::Rational(_) => "rational",
+            Value::Float(_) => "float",
+            Value::Str(_) => "string",
+            Value::Symbol(_) => "symbol",
+            Value::List(_) => "list",
+            Value::Vector(_) => "vector",
+            Value::Map(_) => "map",
+            Value::Set(_) => "set",
+            Value::True | Value::False => "boolean",
+            Value::Nil => "nil",
+            Value::Function(_) => "builtin function",
+            Value::DynFunc(f) if f.is_macro => "macro",
+            Value::DynFunc(_) => "function",
+            Value::Atom(_) => "atom",
+            Value::Error(_) => "error",
+            _ => "special form",
+        }
+    }
+
+    // Conditionals treat everything but `false` and `nil` as true
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::False | Value::Nil)
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, Value::Int(_) | Value::BigInt(_) | Value::Rational(_) | Value::Float(_))
+    }
+
     pub fn is_pair(&self) -> bool {
         match self {
-            Value::List(lst) => lst.len() > 0,
+            Value::List(lst) => !lst.is_empty(),
             _ => false,
         }
     }
 }
 
 
-impl ToString for Value {
-    fn to_string(&self) -> String{
-        match self {
+impl Display for Value {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let str = match self {
             Value::Int (i) => i.to_string(),
-            Value::Str (s) => format!("\"{}\"", s),
+            Value::BigInt (_) | Value::Rational (_) | Value::Float (_) =>
+                numbers::to_string(&Number::from_value(self).unwrap()),
+            Value::Str (s) => parsing::escape_string(s),
             Value::Symbol (s) => s.clone(),
             Value::True => String::from("True"),
             Value::False => String::from("False"),
             Value::Nil => String::from("Nil"),
-            Value::Function (_) =>  String::from("<Builtin-Function>"),
+            Value::Function (f) => format!("<Builtin {}>", f.name),
             Value::List (lst) => list_to_string(lst),
+            Value::Vector (vector) => {
+                let items: Vec<String> = vector.iter().map(|v| v.to_string()).collect();
+                format!("[{}]", items.join(" "))
+            },
+            Value::Map (map) => {
+                let entries: Vec<String> = map.iter().map(|(k, v)| format!("{} {}", k, v)).collect();
+                format!("{{{}}}", entries.join(" "))
+            },
+            Value::Set (set) => {
+                let items: Vec<String> = set.iter().map(|k| k.to_string()).collect();
+                format!("#{{{}}}", items.join(" "))
+            },
             Value::Define => String::from("define"),
             Value::Defmacro => String::from("defmacro"),
+            Value::Macroexpand => String::from("macroexpand"),
+            Value::Macroexpand1 => String::from("macroexpand-1"),
             Value::Let => String::from("let"),
+            Value::LetStar => String::from("let*"),
+            Value::Letrec => String::from("letrec"),
             Value::Do => String::from("do"),
             Value::If => String::from("if"),
             Value::Fn => String::from("fn"),
-            Value::DynFunc (_) => String::from("<Function>"),
+            Value::Defn => String::from("defn"),
+            Value::And => String::from("and"),
+            Value::Or => String::from("or"),
+            Value::Cond => String::from("cond"),
+            Value::Case => String::from("case"),
+            Value::When => String::from("when"),
+            Value::Unless => String::from("unless"),
+            Value::DynFunc (f) => {
+                let kind = if f.is_macro { "Macro" } else { "Function" };
+                match &f.name {
+                    Some(name) => format!("<{} {}>", kind, name),
+                    None => format!("<{}>", kind),
+                }
+            },
             Value::Eval => String::from("eval"),
             Value::Quote => String::from("quote"),
             Value::Quasiquote => String::from("quasiquote"),
             Value::Unquote => String::from("unquote"),
+            Value::UnquoteSplicing => String::from("unquote-splicing"),
             Value::Env => String::from("env"),
+            Value::SetBang => String::from("set!"),
+            Value::Try => String::from("try"),
+            Value::DefineSyntax => String::from("define-syntax"),
+            Value::SyntaxRules => String::from("syntax-rules"),
+            Value::Error (e) => format!("<Error {}: {}>", e.kind, e),
+            Value::Atom (a) => format!("<Atom {}>", a.show_contents(|v| v.to_string())),
+        };
+        write!(fmt, "{}", str)
+    }
+}
+
+// What a function written in Lisp accepts: `(a b &optional (c 1) & more &key d)`.
+// Defaults are expressions, evaluated when the function is called with the
+// earlier parameters in scope; a parameter without one defaults to nil.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Parameters {
+    pub required: Vec<String>,
+    pub optional: Vec<(String, Option<RValue>)>,
+    pub rest: Option<String>,
+    // Passed as `:name value` after the positional arguments
+    pub keys: Vec<(String, Option<RValue>)>,
+}
+
+impl Display for Parameters {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let defaulted = |(name, default): &(String, Option<RValue>)| match default {
+            Some(default) => format!("({} {})", name, default),
+            None => name.clone(),
+        };
+        let mut parts = self.required.clone();
+        if !self.optional.is_empty() {
+            parts.push(String::from("&optional"));
+            parts.extend(self.optional.iter().map(defaulted));
         }
+        if let Some(rest) = &self.rest {
+            parts.push(String::from("&"));
+            parts.push(rest.clone());
+        }
+        if !self.keys.is_empty() {
+            parts.push(String::from("&key"));
+            parts.extend(self.keys.iter().map(defaulted));
+        }
+        write!(fmt, "({})", parts.join(" "))
     }
 }
 
+impl Parameters {
+    pub fn accepts(&self, n: usize) -> bool {
+        let variadic = self.rest.is_some() || !self.keys.is_empty();
+        n >= self.required.len() && (variadic || n <= self.required.len() + self.optional.len())
+    }
+}
+
+// One parameter list and the body it runs
 #[derive(Clone, Debug, PartialEq)]
-pub struct DynamicFunction {
-    pub parameters: LinkedList<String>,
+pub struct Clause {
+    pub parameters: Parameters,
     pub body: RValue,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DynamicFunction {
+    // A call runs the first clause whose parameters fit its arguments
+    pub clauses: Vec<Clause>,
+    // The environment the function was created in, calls extend this scope
+    pub closure: Env,
     pub is_macro: bool,
+    // Given by `defn` or `(fn name ...)`
+    pub name: Option<String>,
+    pub doc: Option<String>,
+    // The form that created the function
+    pub source: Option<Span>,
 }
 
 impl DynamicFunction {
 
-    pub fn new(parameters: LinkedList<String>, body: RValue) -> DynamicFunction {
-        DynamicFunction { parameters : parameters, body: body , is_macro: false}
+    pub fn new(parameters: Parameters, body: RValue, closure: Env)
+               -> DynamicFunction {
+        DynamicFunction {
+            clauses: vec![Clause { parameters, body }],
+            closure,
+            is_macro: false,
+            name: None,
+            doc: None,
+            source: None,
+        }
     }
 
-    pub fn new_macro(parameters: LinkedList<String>, body: RValue)
+    pub fn new_macro(parameters: Parameters, body: RValue, closure: Env)
                      -> DynamicFunction {
-        DynamicFunction { parameters : parameters, body: body , is_macro: true}
+        DynamicFunction { is_macro: true, ..DynamicFunction::new(parameters, body, closure) }
+    }
+
+    // The parameter lists of every clause, for error messages
+    pub fn signature(&self) -> String {
+        let lists: Vec<String> = self.clauses.iter().map(|c| c.parameters.to_string()).collect();
+        lists.join(" or ")
+    }
+}
+
+// A mutable reference. Clones share the same cell, so a change made through
+// one is seen through all of them; two atoms are only equal if they share it.
+#[derive(Clone)]
+pub struct Atom (Rc<RefCell<Value>>);
+
+impl Atom {
+    pub fn new(v: Value) -> Atom {
+        Atom(Rc::new(RefCell::new(v)))
+    }
+
+    pub fn get(&self) -> Value {
+        self.0.borrow().clone()
+    }
+
+    pub fn set(&self, v: Value) {
+        *self.0.borrow_mut() = v;
+    }
+
+    // Shows the atom's value with `show`, or `...` if this atom is already
+    // being shown further out, as it is when an atom contains itself
+    fn show_contents(&self, show: impl FnOnce(&Value) -> String) -> String {
+        let cell = Rc::as_ptr(&self.0);
+        if SHOWING.with(|showing| showing.borrow().contains(&cell)) {
+            return String::from("...");
+        }
+        SHOWING.with(|showing| showing.borrow_mut().push(cell));
+        let shown = show(&self.0.borrow());
+        SHOWING.with(|showing| showing.borrow_mut().pop());
+        shown
+    }
+}
+
+thread_local! {
+    // The atoms whose contents are being printed
+    static SHOWING: RefCell<Vec<*const RefCell<Value>>> = const { RefCell::new(Vec::new()) };
+}
+
+impl PartialEq for Atom {
+    fn eq(&self, other: &Atom) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl std::fmt::Debug for Atom {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(fmt, "Atom({})", self.show_contents(|v| format!("{:?}", v)))
+    }
+}
+
+// How many arguments a builtin accepts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arity {
+    Exactly (usize),
+    AtLeast (usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, n: usize) -> bool {
+        match self {
+            Arity::Exactly(expected) => n == *expected,
+            Arity::AtLeast(min) => n >= *min,
+        }
     }
+}
 
+// How many arguments, for error messages: "2 arguments", "at least 1 argument"
+impl Display for Arity {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let (n, at_least) = match self {
+            Arity::Exactly(n) => (*n, ""),
+            Arity::AtLeast(n) => (*n, "at least "),
+        };
+        write!(fmt, "{}{} argument{}", at_least, n, if n == 1 { "" } else { "s" })
+    }
+}
 
+// A function implemented in Rust
+#[derive(Clone)]
+pub struct Builtin {
+    pub name: String,
+    pub arity: Arity,
+    f: Rc<dyn Fn(Params) -> FResult>,
+}
 
+impl Builtin {
+    pub fn new<F>(name: &str, arity: Arity, f: F) -> Builtin
+        where F: Fn(Params) -> FResult + 'static {
+        Builtin { name: String::from(name), arity, f: Rc::new(f) }
+    }
+
+    pub fn call(&self, args: Params) -> FResult {
+        if !self.arity.accepts(args.len()) {
+            return Err(Errors::SignatureError(self.name.clone(), self.arity.to_string(), args.len()));
+        }
+        (self.f)(args)
+    }
 }
-fn list_to_string(lst: &LinkedList<Box<Value>>) -> String {
+
+impl std::fmt::Debug for Builtin {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(fmt, "<Builtin {}>", self.name)
+    }
+}
+
+impl PartialEq for Builtin {
+    fn eq(&self, other: &Builtin) -> bool {
+        Rc::ptr_eq(&self.f, &other.f)
+    }
+}
+
+fn list_to_string(lst: &List) -> String {
     let empty = String::from("");
     let contents = lst.iter().fold(empty,
-                                   |s, next| format!("{} {}", s, next.to_string()));
+                                   |s, next| format!("{} {}", s, next));
     format!("({})", contents)
 }
 
 
 #[allow(non_snake_case)]
-pub fn PRINT(input: RValue) -> String{
+pub fn PRINT(input: &Value) -> String{
     input.to_string()
 }
 
@@ -0,0 +1,180 @@
+use crate::*;
+use std::iter::FromIterator;
+
+// The contents of `Value::List`: a persistent singly linked list. Lists share
+// their tails, so copying one, adding to its front or taking its rest is
+// O(1) however long it is. Lists read from source code remember where they
+// came from so errors can point back at them; the span plays no part in
+// equality.
+#[derive(Default)]
+pub struct List {
+    head: Option<Rc<Node>>,
+    len: usize,
+    pub span: Option<Span>,
+}
+
+struct Node {
+    value: RValue,
+    next: Option<Rc<Node>>,
+}
+
+impl List {
+    pub fn new() -> List {
+        List::default()
+    }
+
+    pub fn located(items: LinkedList<RValue>, span: Span) -> List {
+        let mut list: List = items.into_iter().collect();
+        list.span = Some(span);
+        list
+    }
+
+    pub fn into_items(self) -> LinkedList<RValue> {
+        self.into_iter().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn front(&self) -> Option<&RValue> {
+        self.head.as_ref().map(|node| &node.value)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter { next: self.head.as_deref() }
+    }
+
+    pub fn push_front(&mut self, value: RValue) {
+        let next = self.head.take();
+        self.head = Some(Rc::new(Node { value, next }));
+        self.len += 1;
+        self.span = None;
+    }
+
+    // Takes the first item, copying it only if another list shares it
+    pub fn pop_front(&mut self) -> Option<RValue> {
+        let node = self.head.take()?;
+        self.len -= 1;
+        self.span = None;
+        match Rc::try_unwrap(node) {
+            Ok(mut node) => {
+                self.head = node.next.take();
+                Some(node.value)
+            },
+            Err(shared) => {
+                self.head = shared.next.clone();
+                Some(shared.value.clone())
+            },
+        }
+    }
+
+    // Everything after the first item, sharing it with this list
+    pub fn tail(&self) -> List {
+        match &self.head {
+            Some(node) => List { head: node.next.clone(), len: self.len - 1, span: None },
+            None => List::new(),
+        }
+    }
+}
+
+impl Clone for List {
+    fn clone(&self) -> List {
+        List { head: self.head.clone(), len: self.len, span: self.span.clone() }
+    }
+}
+
+// Dropping the nodes one at a time, rather than each dropping the next, keeps
+// long lists from overflowing the stack
+impl Drop for List {
+    fn drop(&mut self) {
+        let mut next = self.head.take();
+        while let Some(node) = next {
+            match Rc::try_unwrap(node) {
+                Ok(mut node) => next = node.next.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl PartialEq for List {
+    fn eq(&self, other: &List) -> bool {
+        self.len == other.len && self.iter().zip(other.iter()).all(|(a, b)| a == b)
+    }
+}
+
+impl std::fmt::Debug for List {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        fmt.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl From<LinkedList<RValue>> for List {
+    fn from(items: LinkedList<RValue>) -> List {
+        items.into_iter().collect()
+    }
+}
+
+impl FromIterator<RValue> for List {
+    fn from_iter<I: IntoIterator<Item = RValue>>(iter: I) -> List {
+        let items: Vec<RValue> = iter.into_iter().collect();
+        let mut list = List::new();
+        for item in items.into_iter().rev() {
+            list.push_front(item);
+        }
+        list
+    }
+}
+
+pub struct Iter<'a> {
+    next: Option<&'a Node>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a RValue;
+
+    fn next(&mut self) -> Option<&'a RValue> {
+        let node = self.next?;
+        self.next = node.next.as_deref();
+        Some(&node.value)
+    }
+}
+
+pub struct IntoIter (List);
+
+impl Iterator for IntoIter {
+    type Item = RValue;
+
+    fn next(&mut self) -> Option<RValue> {
+        self.0.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.len, Some(self.0.len))
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+impl IntoIterator for List {
+    type Item = RValue;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        IntoIter(self)
+    }
+}
+
+impl<'a> IntoIterator for &'a List {
+    type Item = &'a RValue;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
@@ -1,71 +1,92 @@
-#[macro_use]
-extern crate nom;
-mod builtins;
-mod parsing;
-mod evals;
-mod stdlib;
-use nom::types::CompleteStr as Input;
+use std::env;
 use std::io;
-use std::io::Write;
+use std::io::{IsTerminal, Read, Write};
 use std::process;
 use rlisp::*;
-use crate::builtins::build_init_env;
-use crate::parsing::*;
-use crate::evals::*;
-use crate::stdlib::*;
 
-fn main() {
-    let stdin = io::stdin();
-    let mut env = build_init_env();
-    match run_std_lib(&mut env) {
-        Ok(_) => (),
-        Err(e) => {
-            println!("Error loading standard library!\n{:?}", e);
-            process::exit(1);
-        }
-    }
+mod repl;
 
-    loop {
-        let mut buffer = String::new();
-        print!("user>");
-        io::stdout().flush().unwrap();
-        stdin.read_line(&mut buffer).unwrap();
-        if buffer.len() == 0 {
-            break;
-        }
-        match rep(buffer, &mut env) {
-            Ok(s) => println!("{}", s),
-            Err(e) => println!("Runtime Error: {}", e),
-        }
-    }
-    print!("\n");
-}
+const USAGE: &str = "Usage: rlisp [file | -e expr | - | -i] [args...]
 
+With no file, runs a REPL, or the program on stdin if it isn't a terminal.
+  file      run the script in file
+  -e expr   evaluate expr and print the result
+  -         run the program on stdin
+  -i        run a REPL even if stdin isn't a terminal
+The remaining args are bound to *argv* as a list of strings.";
 
-fn run_std_lib(env: &mut Env) -> Result<(),Errors> {
-    let fs = get_std_lib();
-    for f in fs {
-        rep(f, env)?;
-    }
-    Ok(())
-}
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut interpreter = Interpreter::new();
+    add_builtins(&mut interpreter);
 
-#[allow(non_snake_case)]
-fn READ(input: String) -> Result<RValue, Errors>{
-    match parse_value(Input(&input)) {
-        Ok(v) => Ok(v.1),
-        Err(pe) => {
-            let msg = pe.to_string();
-            Err(Errors::ParseError(msg))
+    let (program, argv) = match args.split_first() {
+        Some((flag, _)) if flag == "-h" || flag == "--help" => {
+            println!("{}", USAGE);
+            return;
+        },
+        Some((flag, rest)) if flag == "-e" => match rest.split_first() {
+            Some((expr, argv)) => (Program::Expr(expr.clone()), argv),
+            None => {
+                eprintln!("{}", USAGE);
+                process::exit(2);
+            },
         },
+        Some((flag, argv)) if flag == "-" => (Program::Stdin, argv),
+        Some((flag, argv)) if flag == "-i" => (Program::Repl, argv),
+        Some((file, argv)) => (Program::File(file.clone()), argv),
+        None if io::stdin().is_terminal() => (Program::Repl, &args[..]),
+        None => (Program::Stdin, &args[..]),
+    };
+    interpreter.define("*argv*", Value::List(argv.iter().map(|a| Box::new(Value::Str(a.clone()))).collect()));
+
+    if let Err(e) = run(&mut interpreter, program) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
     }
 }
 
+// Builtins only the command line program has. A host embedding the
+// interpreter shouldn't have a script end its process.
+fn add_builtins(interpreter: &mut Interpreter) {
+    interpreter.register("exit", Arity::AtLeast(0), exit);
+}
 
-fn rep(input: String, env: &mut Env) -> Result<String, Errors>{
-    let ast = READ(input)?;
-    let result = EVAL(ast , env)?;
-    Ok(PRINT(result))
+// (exit) or (exit status) ends the process
+fn exit(p: Params) -> FResult {
+    let status = match p.front() {
+        _ if p.len() > 1 => Err(Errors::ArityError(p.len() as i32, 1)),
+        Some(v) => i32::from_lisp(v),
+        None => Ok(0),
+    }?;
+    io::stdout().flush()?;
+    process::exit(status)
 }
 
+enum Program {
+    Repl,
+    Expr(String),
+    File(String),
+    Stdin,
+}
 
+fn run(interpreter: &mut Interpreter, program: Program) -> Result<(), Errors> {
+    match program {
+        Program::Repl => repl::repl(interpreter),
+        Program::Expr(expr) => {
+            let v = interpreter.eval_str(&expr)?;
+            if v != Value::Nil {
+                println!("{}", PRINT(&v));
+            }
+        },
+        Program::File(file) => {
+            interpreter.eval_file(file)?;
+        },
+        Program::Stdin => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            interpreter.eval_program(&text, "<stdin>")?;
+        },
+    }
+    Ok(())
+}
@@ -0,0 +1,309 @@
+use crate::{Value, Errors};
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive, Zero, One, Pow};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+// A number pulled out of a `Value`, ordered from least to most contagious.
+// Arithmetic on two numbers happens at the level of the more contagious one.
+#[derive(Clone, Debug)]
+pub enum Number {
+    Int (i64),
+    Big (BigInt),
+    Ratio (BigRational),
+    Float (f64),
+}
+
+impl Number {
+    pub fn from_value(v: &Value) -> Result<Number, Errors> {
+        match v {
+            Value::Int(i) => Ok(Number::Int(*i)),
+            Value::BigInt(i) => Ok(Number::Big(i.clone())),
+            Value::Rational(r) => Ok(Number::Ratio(r.clone())),
+            Value::Float(f) => Ok(Number::Float(*f)),
+            _ => Err(Errors::type_error("number", v)),
+        }
+    }
+
+    // Converts back to a `Value`, demoting to the simplest exact representation
+    pub fn into_value(self) -> Value {
+        match self {
+            Number::Int(i) => Value::Int(i),
+            Number::Big(i) => match i.to_i64() {
+                Some(i) => Value::Int(i),
+                None => Value::BigInt(i),
+            },
+            Number::Ratio(r) =>
+                if r.is_integer() {
+                    Number::Big(r.to_integer()).into_value()
+                } else {
+                    Value::Rational(r)
+                },
+            Number::Float(f) => Value::Float(f),
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, Number::Float(_))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Int(i) => *i == 0,
+            Number::Big(i) => i.is_zero(),
+            Number::Ratio(r) => r.is_zero(),
+            Number::Float(f) => *f == 0.0,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Int(i) => *i as f64,
+            Number::Big(i) => i.to_f64().unwrap_or(f64::NAN),
+            Number::Ratio(r) => r.to_f64().unwrap_or(f64::NAN),
+            Number::Float(f) => *f,
+        }
+    }
+
+    fn to_big(&self) -> BigInt {
+        match self {
+            Number::Int(i) => BigInt::from(*i),
+            Number::Big(i) => i.clone(),
+            Number::Ratio(r) => r.to_integer(),
+            Number::Float(_) => panic!("to_big() on an inexact number"),
+        }
+    }
+
+    fn to_ratio(&self) -> BigRational {
+        match self {
+            Number::Ratio(r) => r.clone(),
+            Number::Float(_) => panic!("to_ratio() on an inexact number"),
+            i => BigRational::from_integer(i.to_big()),
+        }
+    }
+
+    // Reads the literal syntax for numbers: `-3`, `2.5`, `1e10`, `3/4`
+    pub fn parse(s: &str) -> Option<Number> {
+        let digits = s.strip_prefix('-').or_else(|| s.strip_prefix('+')).unwrap_or(s);
+        if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+            return None;
+        }
+
+        if let Some((n, d)) = s.split_once('/') {
+            let n = parse_integer(n)?;
+            let d = parse_integer(d)?;
+            if d.is_zero() || d.is_negative() {
+                return None;
+            }
+            return Some(Number::Ratio(BigRational::new(n, d)));
+        }
+
+        match parse_integer(s) {
+            Some(i) => Some(Number::Big(i)),
+            None if digits.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c)) =>
+                s.parse::<f64>().ok().map(Number::Float),
+            None => None,
+        }
+    }
+}
+
+fn parse_integer(s: &str) -> Option<BigInt> {
+    let digits = s.strip_prefix('-').or_else(|| s.strip_prefix('+')).unwrap_or(s);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    s.parse::<BigInt>().ok()
+}
+
+// Applies an operation at the contagion level of its two operands, with
+// fixnum overflow promoting to a bignum
+fn binary(a: Number, b: Number,
+          int_op: fn(i64, i64) -> Option<i64>,
+          big_op: fn(BigInt, BigInt) -> BigInt,
+          ratio_op: fn(BigRational, BigRational) -> BigRational,
+          float_op: fn(f64, f64) -> f64) -> Number {
+    match (a, b) {
+        (a @ Number::Float(_), b) | (a, b @ Number::Float(_)) =>
+            Number::Float(float_op(a.to_f64(), b.to_f64())),
+        (a @ Number::Ratio(_), b) | (a, b @ Number::Ratio(_)) =>
+            Number::Ratio(ratio_op(a.to_ratio(), b.to_ratio())),
+        (Number::Int(a), Number::Int(b)) => match int_op(a, b) {
+            Some(i) => Number::Int(i),
+            None => Number::Big(big_op(BigInt::from(a), BigInt::from(b))),
+        },
+        (a, b) => Number::Big(big_op(a.to_big(), b.to_big())),
+    }
+}
+
+fn division_by_zero() -> Errors {
+    Errors::ArithmeticError(String::from("Division by zero"))
+}
+
+pub fn add(a: Number, b: Number) -> Number {
+    binary(a, b, i64::checked_add, |a, b| a + b, |a, b| a + b, |a, b| a + b)
+}
+
+pub fn sub(a: Number, b: Number) -> Number {
+    binary(a, b, i64::checked_sub, |a, b| a - b, |a, b| a - b, |a, b| a - b)
+}
+
+pub fn mul(a: Number, b: Number) -> Number {
+    binary(a, b, i64::checked_mul, |a, b| a * b, |a, b| a * b, |a, b| a * b)
+}
+
+// Exact division yields a rational, so `(/ 1 3)` is `1/3` rather than 0
+pub fn div(a: Number, b: Number) -> Result<Number, Errors> {
+    if b.is_exact() && b.is_zero() {
+        return Err(division_by_zero());
+    }
+    match (a, b) {
+        (a @ Number::Float(_), b) | (a, b @ Number::Float(_)) =>
+            Ok(Number::Float(a.to_f64() / b.to_f64())),
+        (a, b) => Ok(Number::Ratio(a.to_ratio() / b.to_ratio())),
+    }
+}
+
+pub fn rem(a: Number, b: Number) -> Result<Number, Errors> {
+    if b.is_exact() && b.is_zero() {
+        return Err(division_by_zero());
+    }
+    Ok(binary(a, b, i64::checked_rem, |a, b| a % b, |a, b| a % b, |a, b| a % b))
+}
+
+pub fn neg(a: Number) -> Number {
+    sub(Number::Int(0), a)
+}
+
+pub fn abs(a: Number) -> Number {
+    match a {
+        Number::Int(i) => match i.checked_abs() {
+            Some(i) => Number::Int(i),
+            None => Number::Big(BigInt::from(i).abs()),
+        },
+        Number::Big(i) => Number::Big(i.abs()),
+        Number::Ratio(r) => Number::Ratio(r.abs()),
+        Number::Float(f) => Number::Float(f.abs()),
+    }
+}
+
+// `None` when either side is NaN
+pub fn compare(a: &Number, b: &Number) -> Option<Ordering> {
+    match (a, b) {
+        (Number::Float(_), _) | (_, Number::Float(_)) =>
+            a.to_f64().partial_cmp(&b.to_f64()),
+        (Number::Int(a), Number::Int(b)) => Some(a.cmp(b)),
+        (Number::Ratio(_), _) | (_, Number::Ratio(_)) =>
+            Some(a.to_ratio().cmp(&b.to_ratio())),
+        (a, b) => Some(a.to_big().cmp(&b.to_big())),
+    }
+}
+
+pub fn to_inexact(a: Number) -> Number {
+    Number::Float(a.to_f64())
+}
+
+pub fn floor(a: Number) -> Number {
+    match a {
+        Number::Ratio(r) => Number::Ratio(r.floor()),
+        Number::Float(f) => Number::Float(f.floor()),
+        i => i,
+    }
+}
+
+// Rounds to the nearest integer, with ties going to the even neighbour
+pub fn round(a: Number) -> Number {
+    match a {
+        Number::Ratio(r) => {
+            let floor = r.floor();
+            let half = BigRational::new(BigInt::one(), BigInt::from(2));
+            let rounded = match (&r - &floor).cmp(&half) {
+                Ordering::Less => floor,
+                Ordering::Greater => floor + BigRational::one(),
+                Ordering::Equal =>
+                    if floor.to_integer().is_even() {
+                        floor
+                    } else {
+                        floor + BigRational::one()
+                    },
+            };
+            Number::Ratio(rounded)
+        },
+        Number::Float(f) => Number::Float(f.round_ties_even()),
+        i => i,
+    }
+}
+
+// Exact for perfect squares of exact numbers, inexact otherwise
+pub fn sqrt(a: Number) -> Result<Number, Errors> {
+    if compare(&a, &Number::Int(0)) == Some(Ordering::Less) {
+        return Err(Errors::ArithmeticError(String::from("Square root of a negative number")));
+    }
+    let exact = match &a {
+        Number::Float(_) => None,
+        Number::Ratio(r) => exact_sqrt(r.numer()).and_then(|n|
+            exact_sqrt(r.denom()).map(|d| Number::Ratio(BigRational::new(n, d)))),
+        i => exact_sqrt(&i.to_big()).map(Number::Big),
+    };
+    Ok(exact.unwrap_or_else(|| Number::Float(a.to_f64().sqrt())))
+}
+
+fn exact_sqrt(i: &BigInt) -> Option<BigInt> {
+    let root = i.sqrt();
+    if &(&root * &root) == i {
+        Some(root)
+    } else {
+        None
+    }
+}
+
+// Exact powers bigger than this many bits are refused rather than computed
+const MAX_EXPT_BITS: u64 = 1 << 20;
+
+// Exact bases raised to integer powers stay exact
+pub fn expt(base: Number, power: Number) -> Result<Number, Errors> {
+    let exponent = match (&base, &power) {
+        (Number::Float(_), _) => None,
+        (_, Number::Int(i)) => Some(*i),
+        (_, Number::Big(_)) =>
+            return Err(Errors::ArithmeticError(String::from("Exponent too large"))),
+        _ => None,
+    };
+    match exponent {
+        Some(e) => {
+            if e < 0 && base.is_zero() {
+                return Err(division_by_zero());
+            }
+            let magnitude = match u32::try_from(e.unsigned_abs()) {
+                Ok(m) => Ok(m),
+                Err(_) => Err(Errors::ArithmeticError(String::from("Exponent too large"))),
+            }?;
+            let base = base.to_ratio();
+            // The result needs about log2 |n| bits per multiplication, which is
+            // zero or less for 0, 1 and -1 however large the power
+            let log2 = |n: &BigInt| n.to_f64().map_or(f64::INFINITY, |f| f.abs().log2());
+            let bits = log2(base.numer()).max(log2(base.denom())) * f64::from(magnitude);
+            if bits > MAX_EXPT_BITS as f64 {
+                return Err(Errors::ArithmeticError(String::from("Exponent too large")));
+            }
+            let result = Pow::pow(base, magnitude);
+            if e < 0 {
+                Ok(Number::Ratio(result.recip()))
+            } else {
+                Ok(Number::Ratio(result))
+            }
+        },
+        None => Ok(Number::Float(base.to_f64().powf(power.to_f64()))),
+    }
+}
+
+pub fn to_string(n: &Number) -> String {
+    match n {
+        Number::Int(i) => i.to_string(),
+        Number::Big(i) => i.to_string(),
+        Number::Ratio(r) => format!("{}/{}", r.numer(), r.denom()),
+        // Debug formatting keeps the decimal point on whole floats
+        Number::Float(f) => format!("{:?}", f),
+    }
+}
@@ -1,16 +1,193 @@
-use rlisp::*;
+use crate::*;
+use crate::numbers::Number;
 extern crate nom;
 use nom::types::CompleteStr as Input;
+use nom::{Context, Err, ErrorKind, IResult};
 use std::collections::LinkedList;
+use std::rc::Rc;
 
 
 fn is_digit(c: char) -> bool {
-    c.is_digit(10)
+    c.is_ascii_digit()
 }
 
 fn is_alpha(c: char) -> bool {
     c.is_alphabetic() || c == '+' || c == '-' || c == '*' || c == '?' || c == '_'
-        || c == '=' || c == '<' || c == '>'
+        || c == '=' || c == '<' || c == '>' || c == '/' || c == '!' || c == '&' || c == ':'
+}
+
+// Digits may appear in a symbol, just not at the start
+fn is_symbol_char(c: char) -> bool {
+    is_alpha(c) || is_digit(c)
+}
+
+fn is_atom_char(c: char) -> bool {
+    is_symbol_char(c) || c == '.'
+}
+
+// Numbers and symbols share characters (`-`, `+`, `/`), so read the whole
+// token before deciding which one it is
+fn determine_atom(s: &str) -> Result<Value, String> {
+    match Number::parse(s) {
+        Some(n) => Ok(n.into_value()),
+        None if s.starts_with(is_digit) => Err(format!("Invalid number {}", s)),
+        None => Ok(determinte_symbol(s.to_string())),
+    }
+}
+
+#[allow(non_snake_case)]
+pub fn READ(input: String) -> Result<RValue, Errors>{
+    read_source(Source::new(&input, None))
+}
+
+// Reads the one form in `source`, recording where each list came from
+pub fn read_source(source: Rc<Source>) -> Result<RValue, Errors> {
+    let mut forms = read_all(source)?;
+    match forms.len() {
+        1 => Ok(forms.pop_front().unwrap()),
+        n => Err(Errors::ParseError(format!("Expected one form but found {}", n))),
+    }
+}
+
+// Reads every form in `source`, failing if anything other than whitespace
+// and comments is left over. A `#!` line at the very start is ignored so
+// scripts can be made executable.
+pub fn read_all(source: Rc<Source>) -> Result<LinkedList<RValue>, Errors> {
+    match read_forms(&source) {
+        Ok(forms) => Ok(forms),
+        Err(pe) => Err(parse_error(&source, pe)),
+    }
+}
+
+// What reading input that may still be being typed produced
+pub enum Partial {
+    Complete(LinkedList<RValue>),
+    // The input ends inside a form, so more of it is needed
+    Incomplete,
+}
+
+// Like `read_all`, but input that is only wrong because it stops part way
+// through a list, string or comment is incomplete rather than invalid
+pub fn read_partial(input: &str) -> Result<Partial, Errors> {
+    let source = Source::new(input, None);
+    match read_forms(&source) {
+        Ok(forms) => Ok(Partial::Complete(forms)),
+        Err(Err::Failure(Context::Code(_, ErrorKind::Custom(code))))
+            if code == END_OF_INPUT || code == UNTERMINATED_STRING || code == UNTERMINATED_COMMENT =>
+            Ok(Partial::Incomplete),
+        Err(pe) => Err(parse_error(&source, pe)),
+    }
+}
+
+fn read_forms(source: &Rc<Source>) -> Result<LinkedList<RValue>, Err<Input<'_>>> {
+    let mut forms = LinkedList::new();
+    let mut rest = Input(&source.text);
+    if rest.0.starts_with("#!") {
+        rest = Input(rest.0.find('\n').map_or("", |i| &rest.0[i..]));
+    }
+    loop {
+        rest = skip(rest, source)?.0;
+        if rest.0.is_empty() {
+            return Ok(forms);
+        }
+        let (after, form) = parse_value(rest, source)?;
+        forms.push_back(form);
+        rest = after;
+    }
+}
+
+// Describes a failed parse by where in the source it went wrong
+fn parse_error(source: &Rc<Source>, e: Err<Input>) -> Errors {
+    let (input, message) = match e {
+        Err::Failure(Context::Code(input, ErrorKind::Custom(code))) => (input, failure_message(code)),
+        Err::Error(Context::Code(input, _)) | Err::Failure(Context::Code(input, _)) =>
+            (input, "Unexpected input"),
+        Err::Incomplete(_) => (Input(""), "Unexpected end of input"),
+    };
+    let start = source.text.len() - input.0.len();
+    let at = Span { source: source.clone(), start, end: start };
+    Errors::ParseError(format!("{} at {}", message, at))
+}
+
+// Custom error codes for input that can't be recovered from by backtracking
+const UNTERMINATED_STRING: u32 = 1;
+const UNKNOWN_ESCAPE: u32 = 2;
+const UNTERMINATED_COMMENT: u32 = 3;
+const END_OF_INPUT: u32 = 4;
+const UNPAIRED_MAP: u32 = 5;
+const INVALID_KEY: u32 = 6;
+
+fn failure_message(code: u32) -> &'static str {
+    match code {
+        UNTERMINATED_STRING => "Unterminated string",
+        UNKNOWN_ESCAPE => "Unknown escape sequence in string",
+        UNTERMINATED_COMMENT => "Unterminated block comment",
+        END_OF_INPUT => "Unexpected end of input",
+        UNPAIRED_MAP => "Map literal has a key without a value",
+        INVALID_KEY => "Invalid map key or set member",
+        _ => "Invalid input",
+    }
+}
+
+fn failure(input: Input, code: u32) -> Err<Input> {
+    Err::Failure(Context::Code(input, ErrorKind::Custom(code)))
+}
+
+// Every form needs at least one more character
+fn not_at_end(input: Input) -> IResult<Input, ()> {
+    if input.0.is_empty() {
+        Err(failure(input, END_OF_INPUT))
+    } else {
+        Ok((input, ()))
+    }
+}
+
+// How much input is left, which locates the parser within the whole source
+fn remaining(input: Input) -> IResult<Input, usize> {
+    Ok((input, input.0.len()))
+}
+
+// Skips whitespace and comments: `;` to the end of the line, `#| |#` blocks
+// (which nest), and `#_` which discards the form after it
+fn skip<'a>(input: Input<'a>, src: &Rc<Source>) -> IResult<Input<'a>, ()> {
+    let mut rest = input.0;
+    loop {
+        rest = rest.trim_start();
+        if rest.starts_with(';') {
+            rest = rest.find('\n').map_or("", |i| &rest[i..]);
+        } else if rest.starts_with("#|") {
+            let mut depth = 0;
+            let mut i = 0;
+            loop {
+                let here = &rest[i..];
+                if here.starts_with("#|") {
+                    depth += 1;
+                    i += 2;
+                } else if here.starts_with("|#") {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    match here.chars().next() {
+                        Some(c) => i += c.len_utf8(),
+                        None => return Err(failure(Input(rest), UNTERMINATED_COMMENT)),
+                    }
+                }
+            }
+            rest = &rest[i..];
+        } else if let Some(after) = rest.strip_prefix("#_") {
+            rest = parse_value(Input(after), src)?.0 .0;
+        } else {
+            return Ok((Input(rest), ()));
+        }
+    }
+}
+
+fn span_between(src: &Rc<Source>, start_remaining: usize, end_remaining: usize) -> Span {
+    let len = src.text.len();
+    Span { source: src.clone(), start: len - start_remaining, end: len - end_remaining }
 }
 
 fn determinte_symbol(s: String) -> Value {
@@ -19,67 +196,192 @@ fn determinte_symbol(s: String) -> Value {
         "false" => Value::False,
         "nil" => Value::Nil,
         "define" => Value::Define,
+        "defmacro" => Value::Defmacro,
+        "macroexpand" => Value::Macroexpand,
+        "macroexpand-1" => Value::Macroexpand1,
         "let" => Value::Let,
+        "let*" => Value::LetStar,
+        "letrec" => Value::Letrec,
         "do" => Value::Do,
         "if" => Value::If,
         "fn" => Value::Fn,
+        "defn" => Value::Defn,
+        "and" => Value::And,
+        "or" => Value::Or,
+        "cond" => Value::Cond,
+        "case" => Value::Case,
+        "when" => Value::When,
+        "unless" => Value::Unless,
         "eval" => Value::Eval,
         "quote" => Value::Quote,
         "quasiquote" => Value::Quasiquote,
         "unquote" => Value::Unquote,
+        "unquote-splicing" => Value::UnquoteSplicing,
         "env" => Value::Env,
+        "set!" => Value::SetBang,
+        "try" => Value::Try,
+        "define-syntax" => Value::DefineSyntax,
+        "syntax-rules" => Value::SyntaxRules,
         _ => Value::Symbol(s)
     }
 }
 
 
-named!(pub parse_value<Input, RValue>,
-       map!(alt!(parse_string   |
-                 parse_symbol   |
-                 parse_int      |
-                 parse_quote    |
-                 parse_qquote   |
-                 parse_uquote   |
-                 parse_list),
-            |v| Box::new(v)));
+named_args!(pub parse_value<'a>(src: &Rc<Source>)<Input<'a>, RValue>, do_parse!(
+    call!(skip, src) >>
+    not_at_end >>
+    value: alt!(parse_string                |
+                parse_atom                  |
+                call!(parse_quote, src)     |
+                call!(parse_qquote, src)    |
+                call!(parse_uquote_splicing, src) |
+                call!(parse_uquote, src)    |
+                call!(parse_list, src)      |
+                call!(parse_vector, src)    |
+                call!(parse_map, src)       |
+                call!(parse_set, src)) >>
+    call!(skip, src) >>
+    (Box::new(value))));
 
-named!(parse_quote<Input, Value>, ws!(do_parse!(
+named_args!(parse_quote<'a>(src: &Rc<Source>)<Input<'a>, Value>, do_parse!(
     _start: tag!("'") >>
-    quoted: parse_value >>
-    (Value::List(vec![Box::new(Value::Quote), quoted].into_iter().collect())))));
+    quoted: call!(parse_value, src) >>
+    (Value::List(vec![Box::new(Value::Quote), quoted].into_iter().collect()))));
 
-named!(parse_qquote<Input, Value>, ws!(do_parse!(
+named_args!(parse_qquote<'a>(src: &Rc<Source>)<Input<'a>, Value>, do_parse!(
     _stat: tag!("`") >>
-    quoted: parse_value >>
-    (Value::List(vec![Box::new(Value::Quasiquote), quoted].into_iter().collect())))));
+    quoted: call!(parse_value, src) >>
+    (Value::List(vec![Box::new(Value::Quasiquote), quoted].into_iter().collect()))));
+
+named_args!(parse_uquote_splicing<'a>(src: &Rc<Source>)<Input<'a>, Value>, do_parse!(
+    _stat: tag!(",@") >>
+    quoted: call!(parse_value, src) >>
+    (Value::List(vec![Box::new(Value::UnquoteSplicing), quoted].into_iter().collect()))));
 
-named!(parse_uquote<Input, Value>, ws!(do_parse!(
+named_args!(parse_uquote<'a>(src: &Rc<Source>)<Input<'a>, Value>, do_parse!(
     _stat: tag!(",") >>
-    quoted: parse_value >>
-    (Value::List(vec![Box::new(Value::Unquote), quoted].into_iter().collect())))));
+    quoted: call!(parse_value, src) >>
+    (Value::List(vec![Box::new(Value::Unquote), quoted].into_iter().collect()))));
 
-named!(parse_list<Input, Value>, ws!(do_parse!(
+named_args!(parse_list<'a>(src: &Rc<Source>)<Input<'a>, Value>, do_parse!(
+    start: remaining >>
     _start: tag!("(") >>
-        contents: fold_many0!( parse_value, LinkedList::new(),
+    call!(skip, src) >>
+        contents: fold_many0!( call!(parse_value, src), LinkedList::new(),
                                |mut ll: LinkedList<_>, item| {
                                    ll.push_back(item);
                                    ll 
                                }) >> 
     _end: tag!(")") >>
-    (Value::List(contents)))));
-
-named!(parse_int<Input, Value>, ws!(do_parse!(
-    num: map_res!(take_while!(is_digit), 
-                      |Input(s)| s.parse::<i32>()) >>
-        (Value::Int(num)))));
-            
-
-named!(parse_string<Input, Value>, ws!(do_parse!(
-            _start: tag!("\"") >>
-            contents: map!(take_until!("\""), |Input(s)| String::from(s)) >>
-            _end: tag!("\"") >>
-            (Value::Str(contents.into())))));
-
-named!(parse_symbol<Input, Value>, 
-       ws!(map!(take_while1!(is_alpha),
-                |Input(s)| determinte_symbol(s.to_string()))));
+    end: remaining >>
+    (Value::List(List::located(contents, span_between(src, start, end))))));
+
+named_args!(parse_vector<'a>(src: &Rc<Source>)<Input<'a>, Value>, do_parse!(
+    _start: tag!("[") >>
+    call!(skip, src) >>
+    contents: many0!(call!(parse_value, src)) >>
+    _end: tag!("]") >>
+    (Value::Vector(contents.into_iter().map(|v| *v).collect()))));
+
+named_args!(parse_map<'a>(src: &Rc<Source>)<Input<'a>, Value>, do_parse!(
+    _start: tag!("{") >>
+    call!(skip, src) >>
+    contents: many0!(call!(parse_value, src)) >>
+    _end: tag!("}") >>
+    map: call!(to_map, contents) >>
+    (map)));
+
+named_args!(parse_set<'a>(src: &Rc<Source>)<Input<'a>, Value>, do_parse!(
+    _start: tag!("#{") >>
+    call!(skip, src) >>
+    contents: many0!(call!(parse_value, src)) >>
+    _end: tag!("}") >>
+    set: call!(to_set, contents) >>
+    (set)));
+
+// The forms in a map literal, taken as alternating keys and values
+fn to_map(input: Input, contents: Vec<RValue>) -> IResult<Input, Value> {
+    if !contents.len().is_multiple_of(2) {
+        return Err(failure(input, UNPAIRED_MAP));
+    }
+    let mut map = collections::Map::new();
+    let mut contents = contents.into_iter();
+    while let (Some(k), Some(v)) = (contents.next(), contents.next()) {
+        let key = Key::new(*k).map_err(|_| failure(input, INVALID_KEY))?;
+        map.insert(key, *v);
+    }
+    Ok((input, Value::Map(map)))
+}
+
+fn to_set(input: Input, contents: Vec<RValue>) -> IResult<Input, Value> {
+    let set = contents.into_iter().map(|v| Key::new(*v))
+        .collect::<Result<_, _>>()
+        .map_err(|_| failure(input, INVALID_KEY))?;
+    Ok((input, Value::Set(set)))
+}
+
+// A string literal, with `\n`, `\t`, `\r`, `\0`, `\\`, `\"` and `\u{hex}` escapes
+fn parse_string(input: Input) -> IResult<Input, Value> {
+    let (mut rest, _) = tag!(input, "\"")?;
+    let mut contents = String::new();
+    loop {
+        let mut chars = rest.0.chars();
+        match chars.next() {
+            None => return Err(failure(input, UNTERMINATED_STRING)),
+            Some('"') => return Ok((Input(chars.as_str()), Value::Str(contents))),
+            Some('\\') => {
+                let (c, after) = unescape(chars.as_str()).ok_or_else(|| failure(rest, UNKNOWN_ESCAPE))?;
+                contents.push(c);
+                rest = Input(after);
+            },
+            Some(c) => {
+                contents.push(c);
+                rest = Input(chars.as_str());
+            },
+        }
+    }
+}
+
+// The character an escape stands for, given the text after its backslash
+fn unescape(s: &str) -> Option<(char, &str)> {
+    let mut chars = s.chars();
+    let c = match chars.next()? {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        '\\' => '\\',
+        '"' => '"',
+        'u' => {
+            let hex = chars.as_str().strip_prefix('{')?;
+            let close = hex.find('}')?;
+            let c = u32::from_str_radix(&hex[..close], 16).ok().and_then(std::char::from_u32)?;
+            return Some((c, &hex[close + 1..]));
+        },
+        _ => return None,
+    };
+    Some((c, chars.as_str()))
+}
+
+// Writes a string as a literal that reads back as the same string
+pub fn escape_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+named!(parse_atom<Input, Value>, 
+       map_res!(take_while1!(is_atom_char),
+                |Input(s)| determine_atom(s)));
@@ -0,0 +1,292 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+use rlisp::*;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+// Lines are gathered until they make up complete forms. Ctrl-C or end of
+// input part way through a form abandons it, and end of input at the prompt
+// ends the session. Lines starting with the name of a command at the prompt
+// are commands to the REPL itself, see `COMMANDS`; any other line, even one
+// starting with a keyword such as `:a`, is evaluated.
+pub fn repl(interpreter: &mut Interpreter) {
+    let mut session = Session {
+        lines: Lines::new(interpreter.env().clone()),
+        interpreter,
+        transcript: String::new(),
+    };
+    let mut buffer = String::new();
+
+    loop {
+        let prompt = if buffer.is_empty() { "user>" } else { "...>" };
+        match session.lines.read(prompt) {
+            Line::Text(line) => {
+                if let Some((command, arg)) = find_command(&line).filter(|_| buffer.is_empty()) {
+                    session.lines.remember(line.trim());
+                    if !session.command(command, line.trim(), arg) {
+                        break;
+                    }
+                    continue;
+                }
+                buffer.push_str(&line);
+                buffer.push('\n');
+            },
+            Line::Interrupted => {
+                buffer.clear();
+                continue;
+            },
+            Line::Eof if buffer.is_empty() => break,
+            Line::Eof => {
+                buffer.clear();
+                println!();
+                continue;
+            },
+        }
+
+        match read_partial(&buffer) {
+            Ok(Partial::Incomplete) => continue,
+            Ok(Partial::Complete(_)) => {
+                session.lines.remember(buffer.trim_end());
+                let output = match session.interpreter.eval_str(&buffer) {
+                    Ok(v) => PRINT(&v),
+                    Err(e) => format!("Runtime Error: {}", e),
+                };
+                session.respond(buffer.trim_end(), &output);
+            },
+            Err(e) => {
+                session.lines.remember(buffer.trim_end());
+                session.respond(buffer.trim_end(), &format!("Runtime Error: {}", e));
+            },
+        }
+        buffer.clear();
+    }
+    println!();
+}
+
+// A REPL command: the names it goes by, how it's used and what it does for
+// `:help`, and what runs it, giving its output or `None` to end the session
+struct Command {
+    names: &'static [&'static str],
+    usage: &'static str,
+    help: &'static str,
+    run: fn(&mut Session<'_>, &str) -> Option<String>,
+}
+
+const COMMANDS: [Command; 8] = [
+    Command { names: &[":load"], usage: ":load file", help: "evaluate the forms in file", run: |s, arg| s.load(arg) },
+    Command { names: &[":env"], usage: ":env [filter]", help: "list bindings, only those containing filter if given", run: |s, arg| s.bindings(arg) },
+    Command { names: &[":time"], usage: ":time expr", help: "evaluate expr and show how long it took", run: |s, arg| s.time(arg) },
+    Command { names: &[":type"], usage: ":type expr", help: "show the type of the value of expr", run: |s, arg| s.type_of(arg) },
+    Command { names: &[":reset"], usage: ":reset", help: "start again from the builtins and standard library", run: |s, arg| s.reset(arg) },
+    Command { names: &[":save"], usage: ":save file", help: "write this session's inputs and outputs to file", run: |s, arg| s.save(arg) },
+    Command { names: &[":quit", ":q"], usage: ":quit", help: "leave the REPL", run: |_, _| None },
+    Command { names: &[":help"], usage: ":help", help: "show this message", run: |_, _| Some(help()) },
+];
+
+// The command `line` runs, if it's one, and the rest of the line
+fn find_command(line: &str) -> Option<(&'static Command, &str)> {
+    let line = line.trim();
+    let (name, arg) = match line.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (line, ""),
+    };
+    COMMANDS.iter().find(|c| c.names.contains(&name)).map(|c| (c, arg))
+}
+
+fn help() -> String {
+    let lines: Vec<String> = COMMANDS.iter().map(|c| format!("{:<16}{}", c.usage, c.help)).collect();
+    lines.join("\n")
+}
+
+struct Session<'a> {
+    interpreter: &'a mut Interpreter,
+    lines: Lines,
+    // Everything typed and printed so far, for `:save`
+    transcript: String,
+}
+
+impl Session<'_> {
+    fn respond(&mut self, input: &str, output: &str) {
+        println!("{}", output);
+        self.transcript.push_str(&format!("user>{}\n{}\n", input, output));
+    }
+
+    // Runs a `:command`, returning false if the session should end
+    fn command(&mut self, command: &Command, line: &str, arg: &str) -> bool {
+        match (command.run)(self, arg) {
+            Some(output) => {
+                self.respond(line, &output);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn load(&mut self, file: &str) -> Option<String> {
+        Some(match self.interpreter.eval_file(file) {
+            Ok(v) => PRINT(&v),
+            Err(e) => format!("Runtime Error: {}", e),
+        })
+    }
+
+    fn time(&mut self, expr: &str) -> Option<String> {
+        let started = Instant::now();
+        let result = self.interpreter.eval_str(expr);
+        let elapsed = started.elapsed();
+        Some(match result {
+            Ok(v) => format!("{}\nElapsed: {:?}", PRINT(&v), elapsed),
+            Err(e) => format!("Runtime Error: {}\nElapsed: {:?}", e, elapsed),
+        })
+    }
+
+    fn type_of(&mut self, expr: &str) -> Option<String> {
+        Some(match self.interpreter.eval_str(expr) {
+            Ok(v) => String::from(v.type_name()),
+            Err(e) => format!("Runtime Error: {}", e),
+        })
+    }
+
+    fn reset(&mut self, _: &str) -> Option<String> {
+        let argv = self.interpreter.lookup("*argv*");
+        *self.interpreter = Interpreter::new();
+        crate::add_builtins(self.interpreter);
+        if let Ok(argv) = argv {
+            self.interpreter.define("*argv*", argv);
+        }
+        self.lines.complete_from(self.interpreter.env().clone());
+        Some(String::from("Environment reset"))
+    }
+
+    fn save(&mut self, file: &str) -> Option<String> {
+        Some(match fs::write(file, &self.transcript) {
+            Ok(()) => format!("Transcript saved to {}", file),
+            Err(e) => format!("Could not save transcript: {}", e),
+        })
+    }
+
+    // Each binding whose name contains `filter`, with the type of its value
+    fn bindings(&mut self, filter: &str) -> Option<String> {
+        let env = self.interpreter.env();
+        let lines: Vec<String> = env.symbols().into_iter()
+            .filter(|name| name.contains(filter))
+            .map(|name| {
+                let kind = env.lookup(name.clone()).map_or("", |v| v.type_name());
+                format!("{:<24}{}", name, kind)
+            })
+            .collect();
+        Some(lines.join("\n"))
+    }
+}
+
+enum Line {
+    Text(String),
+    Interrupted,
+    Eof,
+}
+
+// Where REPL input comes from: a line editor when talking to a terminal,
+// otherwise plain stdin
+enum Lines {
+    Editor(Box<Editor<Completions, FileHistory>>, Option<PathBuf>),
+    Plain,
+}
+
+impl Lines {
+    fn new(env: Env) -> Lines {
+        if !io::stdin().is_terminal() {
+            return Lines::Plain;
+        }
+        match Editor::new() {
+            Ok(mut editor) => {
+                editor.set_helper(Some(Completions { env }));
+                let history = history_file();
+                if let Some(path) = &history {
+                    // There is no history the first time round
+                    let _ = editor.load_history(path);
+                }
+                Lines::Editor(Box::new(editor), history)
+            },
+            Err(_) => Lines::Plain,
+        }
+    }
+
+    fn read(&mut self, prompt: &str) -> Line {
+        match self {
+            Lines::Editor(editor, _) => match editor.readline(prompt) {
+                Ok(line) => Line::Text(line),
+                Err(ReadlineError::Interrupted) => Line::Interrupted,
+                Err(_) => Line::Eof,
+            },
+            Lines::Plain => {
+                print!("{}", prompt);
+                io::stdout().flush().unwrap();
+                let mut line = String::new();
+                match io::stdin().lock().read_line(&mut line) {
+                    Ok(0) | Err(_) => Line::Eof,
+                    Ok(_) => Line::Text(String::from(line.trim_end_matches('\n'))),
+                }
+            },
+        }
+    }
+
+    fn complete_from(&mut self, env: Env) {
+        if let Lines::Editor(editor, _) = self {
+            editor.set_helper(Some(Completions { env }));
+        }
+    }
+
+    // Adds an entry to the history, saving it straight away so that it
+    // survives the session ending through `exit`
+    fn remember(&mut self, entry: &str) {
+        if let Lines::Editor(editor, history) = self {
+            let _ = editor.add_history_entry(entry);
+            if let Some(path) = history {
+                let _ = editor.append_history(path);
+            }
+        }
+    }
+}
+
+fn history_file() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".rlisp_history"))
+}
+
+// Tab completes symbols bound in the session's environment
+struct Completions {
+    env: Env,
+}
+
+fn ends_symbol(c: char) -> bool {
+    c.is_whitespace() || "()'`,\"".contains(c)
+}
+
+impl Completer for Completions {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].char_indices().rev()
+            .find(|&(_, c)| ends_symbol(c))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let prefix = &line[start..pos];
+        let matches = self.env.symbols().into_iter().filter(|s| s.starts_with(prefix)).collect();
+        Ok((start, matches))
+    }
+}
+
+impl Hinter for Completions {
+    type Hint = String;
+}
+
+impl Highlighter for Completions {}
+
+impl Validator for Completions {}
+
+impl Helper for Completions {}
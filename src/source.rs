@@ -0,0 +1,64 @@
+use std::fmt::Display;
+use std::rc::Rc;
+
+// Text handed to the reader, and the file it came from if any
+pub struct Source {
+    pub file: Option<String>,
+    pub text: String,
+}
+
+impl Source {
+    pub fn new(text: &str, file: Option<&str>) -> Rc<Source> {
+        Rc::new(Source { file: file.map(String::from), text: String::from(text) })
+    }
+}
+
+// The region of a source a form was read from. Line and column are only
+// worked out when a span is displayed, which only happens on errors.
+#[derive(Clone)]
+pub struct Span {
+    pub source: Rc<Source>,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    // 1-based line and column of the start of the span
+    pub fn line_col(&self) -> (usize, usize) {
+        let before = &self.source.text[..self.start];
+        let line = before.matches('\n').count() + 1;
+        let column = match before.rfind('\n') {
+            Some(newline) => before[newline + 1..].chars().count() + 1,
+            None => before.chars().count() + 1,
+        };
+        (line, column)
+    }
+
+    // The source text of the form
+    pub fn text(&self) -> &str {
+        self.source.text[self.start..self.end].trim_end()
+    }
+}
+
+// Spans are equal when they cover the same text of the same source
+impl PartialEq for Span {
+    fn eq(&self, other: &Span) -> bool {
+        Rc::ptr_eq(&self.source, &other.source) && self.start == other.start && self.end == other.end
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let (line, column) = self.line_col();
+        match &self.source.file {
+            Some(file) => write!(fmt, "{}:{}:{}", file, line, column),
+            None => write!(fmt, "<input>:{}:{}", line, column),
+        }
+    }
+}
+
+impl std::fmt::Debug for Span {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{}", self)
+    }
+}
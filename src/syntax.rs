@@ -0,0 +1,271 @@
+use crate::*;
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static GENSYM_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// A fresh symbol. The `#:` prefix can't be read, so no symbol in source code
+// is ever equal to one.
+pub fn gensym(prefix: &str) -> String {
+    format!("#:{}{}", prefix, GENSYM_COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+// What a pattern variable matched. Variables under an ellipsis match once
+// per repetition.
+#[derive(Clone)]
+enum Binding {
+    One (Value),
+    Many (Vec<Binding>),
+}
+
+type Bindings = HashMap<String, Binding>;
+
+fn is_ellipsis(v: &Value) -> bool {
+    matches!(v, Value::Symbol(s) if s == "...")
+}
+
+// The rules of a `syntax-rules` form: `(syntax-rules (literal...) (pattern template)...)`
+pub struct SyntaxRules {
+    literals: Vec<String>,
+    rules: Vec<(Value, Value)>,
+}
+
+impl SyntaxRules {
+    pub fn parse(list: LinkedList<RValue>) -> Result<SyntaxRules, Errors> {
+        let mut list = list;
+        list.pop_front(); // Remove `syntax-rules`
+        let literals = match list.pop_front().map(|b| *b) {
+            Some(Value::List(literals)) => literals.into_iter().map(|l| match *l {
+                Value::Symbol(s) => Ok(s),
+                v => Err(Errors::type_error("symbol", &v)),
+            }).collect::<Result<_, _>>(),
+            _ => Err(Errors::FormError(String::from("syntax-rules expects a list of literals"))),
+        }?;
+        let rules = list.into_iter().map(|rule| match *rule {
+            Value::List(rule) if rule.len() == 2 && matches!(rule.front().map(|b| b.deref()), Some(Value::List(_))) => {
+                let mut rule = rule.into_items();
+                Ok((*rule.pop_front().unwrap(), *rule.pop_front().unwrap()))
+            },
+            _ => Err(Errors::FormError(String::from("syntax-rules expects rules of the form ((_ pattern...) template)"))),
+        }).collect::<Result<_, _>>()?;
+        Ok(SyntaxRules { literals, rules })
+    }
+
+    // Rewrites a use of the macro, given its arguments, with the template of
+    // the first rule whose pattern they match
+    pub fn expand(&self, args: &List) -> FResult {
+        for (pattern, template) in &self.rules {
+            // The head of the pattern stands for the macro's name
+            let pattern = match pattern {
+                Value::List(pattern) => pattern.iter().skip(1).cloned().collect(),
+                _ => List::new(),
+            };
+            let mut bindings = Bindings::new();
+            if self.matches(&Value::List(pattern), &Value::List(args.clone()), &mut bindings) {
+                let renames = introduced_bindings(template).into_iter()
+                    .filter(|name| !bindings.contains_key(name))
+                    .map(|name| { let renamed = gensym(&name); (name, renamed) })
+                    .collect();
+                return Ok(Box::new(instantiate(template, &bindings, &renames)?));
+            }
+        }
+        Err(Errors::FormError(format!("No syntax-rules pattern matches {}", Value::List(args.clone()))))
+    }
+
+    fn matches(&self, pattern: &Value, form: &Value, bindings: &mut Bindings) -> bool {
+        match (pattern, form) {
+            (Value::Symbol(s), _) if s == "_" => true,
+            (Value::Symbol(s), _) if self.literals.contains(s) =>
+                matches!(form, Value::Symbol(f) if f == s),
+            (Value::Symbol(s), _) => {
+                bindings.insert(s.clone(), Binding::One(form.clone()));
+                true
+            },
+            (Value::List(pattern), Value::List(form)) => {
+                let pattern: Vec<&Value> = pattern.iter().map(|b| b.deref()).collect();
+                let form: Vec<&Value> = form.iter().map(|b| b.deref()).collect();
+                match pattern.iter().position(|p| is_ellipsis(p)) {
+                    Some(e) if e > 0 => {
+                        let (before, repeated, after) = (&pattern[..e - 1], pattern[e - 1], &pattern[e + 1..]);
+                        if form.len() < before.len() + after.len() {
+                            return false;
+                        }
+                        let middle = &form[before.len()..form.len() - after.len()];
+                        self.matches_each(before, &form[..before.len()], bindings)
+                            && self.matches_each(after, &form[form.len() - after.len()..], bindings)
+                            && self.matches_repeated(repeated, middle, bindings)
+                    },
+                    _ => form.len() == pattern.len() && self.matches_each(&pattern, &form, bindings),
+                }
+            },
+            (pattern, form) => pattern == form || Value::equals(pattern, form).unwrap_or(false),
+        }
+    }
+
+    fn matches_each(&self, patterns: &[&Value], forms: &[&Value], bindings: &mut Bindings) -> bool {
+        patterns.iter().zip(forms).all(|(p, f)| self.matches(p, f, bindings))
+    }
+
+    fn matches_repeated(&self, pattern: &Value, forms: &[&Value], bindings: &mut Bindings) -> bool {
+        let mut each = Vec::new();
+        for form in forms {
+            let mut b = Bindings::new();
+            if !self.matches(pattern, form, &mut b) {
+                return false;
+            }
+            each.push(b);
+        }
+        for var in self.variables(pattern) {
+            let matched = each.iter().map(|b| b[&var].clone()).collect();
+            bindings.insert(var, Binding::Many(matched));
+        }
+        true
+    }
+
+    // The pattern variables in a pattern
+    fn variables(&self, pattern: &Value) -> Vec<String> {
+        match pattern {
+            Value::Symbol(s) if s != "_" && !is_ellipsis(pattern) && !self.literals.contains(s) =>
+                vec![s.clone()],
+            Value::List(lst) => lst.iter().flat_map(|p| self.variables(p)).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+// Fills in a template. `renames` maps the names the template binds itself to
+// fresh symbols, so they can't capture or shadow the caller's variables.
+// Any other name is left as it is, and so is looked up where the macro is
+// used rather than where it was defined.
+fn instantiate(template: &Value, bindings: &Bindings, renames: &HashMap<String, String>) -> Result<Value, Errors> {
+    match template {
+        Value::Symbol(s) => match bindings.get(s) {
+            Some(Binding::One(v)) => Ok(v.clone()),
+            Some(Binding::Many(_)) =>
+                Err(Errors::FormError(format!("Pattern variable {} must be followed by ...", s))),
+            None => Ok(Value::Symbol(renames.get(s).unwrap_or(s).clone())),
+        },
+        Value::List(lst) => {
+            let items: Vec<&Value> = lst.iter().map(|b| b.deref()).collect();
+            let mut built = LinkedList::new();
+            let mut i = 0;
+            while i < items.len() {
+                if items.get(i + 1).is_some_and(|v| is_ellipsis(v)) {
+                    for value in instantiate_repeated(items[i], bindings, renames)? {
+                        built.push_back(Box::new(value));
+                    }
+                    i += 2;
+                } else {
+                    built.push_back(Box::new(instantiate(items[i], bindings, renames)?));
+                    i += 1;
+                }
+            }
+            Ok(Value::List(built.into()))
+        },
+        v => Ok(v.clone()),
+    }
+}
+
+// A template followed by `...`, filled in once for each repetition of the
+// pattern variables in it
+fn instantiate_repeated(template: &Value, bindings: &Bindings, renames: &HashMap<String, String>) -> Result<Vec<Value>, Errors> {
+    let used = symbols(template);
+    let repeated: Vec<(&String, &Vec<Binding>)> = bindings.iter().filter_map(|(name, b)| match b {
+        Binding::Many(each) if used.contains(name) => Some((name, each)),
+        _ => None,
+    }).collect();
+    let count = match repeated.first() {
+        Some((_, each)) => each.len(),
+        None => return Err(Errors::FormError(String::from("... follows a template without repeated pattern variables"))),
+    };
+    if repeated.iter().any(|(_, each)| each.len() != count) {
+        return Err(Errors::FormError(String::from("Pattern variables under ... matched different numbers of forms")));
+    }
+    (0..count).map(|i| {
+        let mut these = bindings.clone();
+        for (name, each) in &repeated {
+            these.insert((*name).clone(), each[i].clone());
+        }
+        instantiate(template, &these, renames)
+    }).collect()
+}
+
+fn symbols(template: &Value) -> HashSet<String> {
+    match template {
+        Value::Symbol(s) => vec![s.clone()].into_iter().collect(),
+        Value::List(lst) => lst.iter().flat_map(|t| symbols(t)).collect(),
+        _ => HashSet::new(),
+    }
+}
+
+// The names bound by `let`, `let*`, `letrec`, `fn` and `catch` forms in a
+// template, apart from keyword parameters
+fn introduced_bindings(template: &Value) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let lst = match template {
+        Value::List(lst) => lst,
+        _ => return names,
+    };
+    let items: Vec<&Value> = lst.iter().map(|b| b.deref()).collect();
+    match items.first() {
+        Some(Value::Let) | Some(Value::LetStar) | Some(Value::Letrec) => {
+            if let Some(Value::List(bindings)) = items.get(1) {
+                let pairs = matches!(bindings.front().map(|b| b.deref()), Some(Value::List(_)));
+                for (i, binding) in bindings.iter().enumerate() {
+                    match binding.deref() {
+                        Value::List(pair) if pairs => names.extend(pair.front().and_then(|n| symbol_name(n))),
+                        Value::Symbol(s) if !pairs && i % 2 == 0 => { names.insert(s.clone()); },
+                        _ => (),
+                    }
+                }
+            }
+        },
+        Some(Value::Fn) => {
+            let rest = match items.get(1) {
+                Some(Value::Symbol(name)) => {
+                    names.insert(name.clone());
+                    &items[2..]
+                },
+                _ => &items[1..],
+            };
+            // Several clauses are each `((params...) body...)`, one is `(params...) body...`
+            let multi = matches!(rest.first(), Some(Value::List(first))
+                                 if matches!(first.front().map(|b| b.deref()), Some(Value::List(_))));
+            let param_lists: Vec<&Value> = if multi {
+                rest.iter().filter_map(|clause| match clause {
+                    Value::List(clause) => clause.front().map(|b| b.deref()),
+                    _ => None,
+                }).collect()
+            } else {
+                rest.iter().take(1).copied().collect()
+            };
+            for params in param_lists {
+                if let Value::List(params) = params {
+                    // A keyword parameter's name is also its keyword, so
+                    // renaming it would change how callers pass it
+                    for param in params.iter().take_while(|p| !matches!(&***p, Value::Symbol(s) if s == "&key")) {
+                        match param.deref() {
+                            Value::Symbol(s) if !s.starts_with('&') => { names.insert(s.clone()); },
+                            Value::List(defaulted) => names.extend(defaulted.front().and_then(|n| symbol_name(n))),
+                            _ => (),
+                        }
+                    }
+                }
+            }
+        },
+        Some(Value::Symbol(s)) if s == "catch" => names.extend(items.get(1).and_then(|n| symbol_name(n))),
+        _ => (),
+    }
+    for item in items {
+        names.extend(introduced_bindings(item));
+    }
+    names
+}
+
+fn symbol_name(v: &Value) -> Option<String> {
+    match v {
+        Value::Symbol(s) => Some(s.clone()),
+        _ => None,
+    }
+}
@@ -0,0 +1,51 @@
+use rlisp::*;
+
+#[test]
+fn atoms_print_their_value() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(lisp.eval_str("(atom (list 1 2))").unwrap().to_string(), "<Atom ( 1 2)>");
+}
+
+#[test]
+fn atom_containing_itself_prints() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define a (atom 0))").unwrap();
+    lisp.eval_str("(reset! a a)").unwrap();
+    let a = lisp.eval_str("a").unwrap();
+    assert_eq!(a.to_string(), "<Atom <Atom ...>>");
+    assert_eq!(format!("{:?}", a), "Atom(Atom(Atom(Atom(...))))");
+    lisp.eval_str("(define b (atom nil))").unwrap();
+    lisp.eval_str("(reset! b (list 1 b))").unwrap();
+    assert_eq!(lisp.eval_str("b").unwrap().to_string(), "<Atom ( 1 <Atom ...>)>");
+}
+
+#[test]
+fn set_only_changes_existing_bindings() {
+    let mut lisp = Interpreter::new();
+    match lisp.eval_str("(set! nope 1)") {
+        Err(e) => assert!(matches!(e.root(), Errors::SymbolNotFound(s) if s == "nope")),
+        Ok(v) => panic!("set! returned {}", v),
+    }
+    // and doesn't define the name as a side effect
+    assert!(lisp.eval_str("nope").is_err());
+}
+
+#[test]
+fn set_in_a_closure_changes_the_outer_binding() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define n 0)").unwrap();
+    lisp.eval_str("(define bump (fn (by) (set! n (+ n by))))").unwrap();
+    lisp.eval_str("(bump 2)").unwrap();
+    lisp.eval_str("(bump 3)").unwrap();
+    assert_eq!(lisp.eval_str("n").unwrap(), Value::Int(5));
+    // Scope is lexical: a caller's local of the same name is left alone
+    assert_eq!(lisp.eval_str("(let ((n 10)) (bump 1) n)").unwrap(), Value::Int(10));
+    assert_eq!(lisp.eval_str("n").unwrap(), Value::Int(6));
+    // A closure over a call's local changes that local, which lives on
+    lisp.eval_str("(define make-counter (fn () (let ((count 0)) (fn () (set! count (+ count 1)) count))))").unwrap();
+    lisp.eval_str("(define a (make-counter))").unwrap();
+    lisp.eval_str("(define b (make-counter))").unwrap();
+    lisp.eval_str("(a)").unwrap();
+    assert_eq!(lisp.eval_str("(a)").unwrap(), Value::Int(2));
+    assert_eq!(lisp.eval_str("(b)").unwrap(), Value::Int(1));
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use rlisp::*;
+use std::rc::Rc;
+use common::{assert_evals_to, error_text};
+
+// Registers `make-token`, which returns a builtin holding a reference to
+// `tracker`, so the tracker's count says how many tokens are still alive
+fn tracked() -> (Interpreter, Rc<()>) {
+    let mut lisp = Interpreter::new();
+    let tracker = Rc::new(());
+    let held = tracker.clone();
+    lisp.register("make-token", Arity::Exactly(0), move |_| {
+        let held = held.clone();
+        Ok(Box::new(Value::Function(Builtin::new("token", Arity::Exactly(0), move |_| {
+            let _ = &held;
+            Ok(Box::new(Value::Nil))
+        }))))
+    });
+    (lisp, tracker)
+}
+
+fn call_100_times(lisp: &mut Interpreter, f: &str) {
+    lisp.eval_str(&format!("(define f {})", f)).unwrap();
+    lisp.eval_str("(define loop (fn (i) (if (= i 100) i (do (f i) (loop (+ i 1))))))").unwrap();
+    lisp.eval_str("(loop 0)").unwrap();
+}
+
+#[test]
+fn scope_with_a_local_define_is_freed() {
+    let (mut lisp, tracker) = tracked();
+    call_100_times(&mut lisp, "(fn (n) (do (define t (make-token)) (define h (fn (x) x)) (h n)))");
+    // The tracker itself and the `make-token` builtin
+    assert_eq!(Rc::strong_count(&tracker), 2);
+}
+
+#[test]
+fn scope_with_letrec_locals_is_freed() {
+    let (mut lisp, tracker) = tracked();
+    call_100_times(&mut lisp, "(fn (n) (letrec ((t (make-token)) (ev (fn (x) (if (= x 0) true (od (- x 1))))) (od (fn (x) (if (= x 0) false (ev (- x 1)))))) (ev n)))");
+    assert_eq!(Rc::strong_count(&tracker), 2);
+}
+
+#[test]
+fn scope_with_a_named_fn_is_freed() {
+    let (mut lisp, tracker) = tracked();
+    call_100_times(&mut lisp, "(fn (n) (let ((t (make-token))) ((fn down (i) (if (= i 0) n (down (- i 1)))) 3)))");
+    assert_eq!(Rc::strong_count(&tracker), 2);
+}
+
+#[test]
+fn local_functions_outlive_the_call_that_defined_them() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define make-adder (fn (n) (do (define add (fn (x) (+ x n))) add)))").unwrap();
+    lisp.eval_str("(define add5 (make-adder 5))").unwrap();
+    assert_eq!(lisp.eval_str("(add5 1)").unwrap(), Value::Int(6));
+    lisp.eval_str("(define counter (fn () (letrec ((n 0) (next (fn () (do (set! n (+ n 1)) n)))) next)))").unwrap();
+    lisp.eval_str("(define c (counter))").unwrap();
+    lisp.eval_str("(c)").unwrap();
+    assert_eq!(lisp.eval_str("(c)").unwrap(), Value::Int(2));
+}
+
+#[test]
+fn named_functions_call_themselves() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define fact (fn fact (n) (if (= n 0) 1 (* n (fact (- n 1))))))").unwrap();
+    assert_eq!(lisp.eval_str("(fact 5)").unwrap(), Value::Int(120));
+    lisp.eval_str("(defn count-down (n) (if (= n 0) :done (count-down (- n 1))))").unwrap();
+    assert_eq!(lisp.eval_str("(count-down 3)").unwrap(), Value::Symbol(String::from(":done")));
+    // Parameters shadow the function's own name
+    assert_eq!(lisp.eval_str("((fn self (self) self) 7)").unwrap(), Value::Int(7));
+}
+
+#[test]
+fn registered_functions_are_freed_with_the_interpreter() {
+    let (lisp, tracker) = tracked();
+    drop(lisp);
+    assert_eq!(Rc::strong_count(&tracker), 1);
+}
+
+#[test]
+fn returned_closures_are_freed_with_the_interpreter() {
+    let (mut lisp, tracker) = tracked();
+    lisp.eval_str("(define make-adder (fn (n) (let ((t (make-token))) (fn (x) (+ x n)))))").unwrap();
+    lisp.eval_str("(define add5 (make-adder 5))").unwrap();
+    lisp.eval_str("(define adders (list (make-adder 1) (make-adder 2)))").unwrap();
+    assert_eq!(lisp.eval_str("(add5 1)").unwrap(), Value::Int(6));
+    assert_eq!(Rc::strong_count(&tracker), 5);
+    drop(lisp);
+    assert_eq!(Rc::strong_count(&tracker), 1);
+}
+
+#[test]
+fn clauses_are_chosen_by_the_number_of_arguments() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(defn area ((s) (* s s)) ((w h) (* w h)))").unwrap();
+    assert_evals_to(&mut lisp, "(area 3)", "9");
+    assert_evals_to(&mut lisp, "(area 2 5)", "10");
+    assert_evals_to(&mut lisp, "((fn ((x) 'one) ((x & more) 'many)) 1 2 3)", "'many");
+    assert!(error_text(lisp.eval_str("(area 1 2 3)"))
+            .contains("area expects (s) or (w h), recieved 3 arguments"));
+    assert!(error_text(lisp.eval_str("(area)"))
+            .contains("area expects (s) or (w h), recieved 0 arguments"));
+}
+
+#[test]
+fn defn_keeps_its_docstring_and_source() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(defn area \"Area of a square or rectangle\" ((s) (* s s)) ((w h) (* w h)))").unwrap();
+    assert_evals_to(&mut lisp, "(doc area)", "\"Area of a square or rectangle\"");
+    assert_evals_to(&mut lisp, "(source area)",
+                    r#""(defn area \"Area of a square or rectangle\" ((s) (* s s)) ((w h) (* w h)))""#);
+    lisp.eval_str("(defn plain (x) x)").unwrap();
+    assert_evals_to(&mut lisp, "(doc plain)", "nil");
+}
@@ -0,0 +1,85 @@
+use rlisp::*;
+use std::collections::HashMap;
+
+#[test]
+fn list_processing_scales_to_long_lists() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define build (fn (i n acc) (if (= i n) acc (build (+ i 1) n (cons i acc)))))").unwrap();
+    lisp.eval_str("(define xs (build 0 30000 '()))").unwrap();
+    assert_eq!(lisp.eval_str("(count (map (fn (x) (+ x 1)) xs))").unwrap(), Value::Int(30000));
+    assert_eq!(lisp.eval_str("(foldl + 0 xs)").unwrap(), Value::Int(449985000));
+}
+
+#[test]
+fn changed_collections_leave_the_original_alone() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define xs (list 1 2 3))").unwrap();
+    lisp.eval_str("(define v [1 2 3])").unwrap();
+    lisp.eval_str("(define m {:a 1})").unwrap();
+    lisp.eval_str("(define s #{1})").unwrap();
+    assert_eq!(lisp.eval_str("(cons 0 xs)").unwrap().to_string(), "( 0 1 2 3)");
+    assert_eq!(lisp.eval_str("(assoc v 0 9)").unwrap().to_string(), "[9 2 3]");
+    assert_eq!(lisp.eval_str("(assoc m :b 2)").unwrap().to_string(), "{:a 1 :b 2}");
+    assert_eq!(lisp.eval_str("(conj s 2)").unwrap().to_string(), "#{1 2}");
+    assert_eq!(lisp.eval_str("(list xs v m s)").unwrap().to_string(), "( ( 1 2 3) [1 2 3] {:a 1} #{1})");
+}
+
+#[test]
+fn long_lists_can_be_dropped() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define build (fn (i acc) (if (= i 0) acc (build (- i 1) (cons i acc)))))").unwrap();
+    lisp.eval_str("(define xs (build 200000 '()))").unwrap();
+    lisp.eval_str("(define xs nil)").unwrap();
+}
+
+#[test]
+fn nan_keys_are_ordered_after_other_numbers() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define nan (/ 0.0 0.0))").unwrap();
+    assert_eq!(lisp.eval_str("(count (hash-set 1 nan 2 3))").unwrap(), Value::Int(4));
+    assert_eq!(lisp.eval_str("(count (hash-set nan 1 nan))").unwrap(), Value::Int(2));
+    lisp.eval_str("(define m (hash-map 1 :one nan :nan 2 :two))").unwrap();
+    assert_eq!(lisp.eval_str("(get m 1)").unwrap(), Value::Symbol(String::from(":one")));
+    assert_eq!(lisp.eval_str("(get m 2)").unwrap(), Value::Symbol(String::from(":two")));
+    assert_eq!(lisp.eval_str("(count m)").unwrap(), Value::Int(3));
+}
+
+#[test]
+fn values_of_any_type_can_be_compared() {
+    let mut lisp = Interpreter::new();
+    for (expr, expected) in &[("(= [:a] [:a])", true), ("(= :a :a)", true), ("(= :a :b)", false),
+                              ("(= nil nil)", true), ("(= (gensym) (gensym))", false),
+                              ("(= [1 2] '(1 2))", false), ("(= 1 \"1\")", false), ("(= nil false)", false),
+                              ("(= {:a [1 nil]} {:a [1.0 nil]})", true)] {
+        let expected = if *expected { Value::True } else { Value::False };
+        assert_eq!(lisp.eval_str(expr).unwrap(), expected, "{}", expr);
+    }
+}
+
+#[test]
+fn functions_are_equal_only_to_themselves() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define f (fn (x) x))").unwrap();
+    lisp.eval_str("(define g f)").unwrap();
+    lisp.eval_str("(defmacro m (fn (x) x))").unwrap();
+    for (expr, expected) in &[("(= f f)", true), ("(= f g)", true), ("(= f (fn (x) x))", false),
+                              ("(= + +)", true), ("(= + -)", false), ("(= + f)", false),
+                              ("(= m m)", true), ("(= m f)", false), ("(= [f] [g])", true)] {
+        let expected = if *expected { Value::True } else { Value::False };
+        assert_eq!(lisp.eval_str(expr).unwrap(), expected, "{}", expr);
+    }
+}
+
+#[test]
+fn hash_maps_convert_to_and_from_maps() {
+    let mut lisp = Interpreter::new();
+    lisp.register_fn("scores", || {
+        let mut scores = HashMap::new();
+        scores.insert(String::from("ann"), 3);
+        scores
+    });
+    lisp.register_fn("total", |scores: HashMap<String, i64>| scores.values().sum::<i64>());
+    assert_eq!(lisp.eval_str("(scores)").unwrap(), lisp.eval_str("{\"ann\" 3}").unwrap());
+    assert_eq!(lisp.eval_str("(total {\"a\" 1 \"b\" 2})").unwrap(), Value::Int(3));
+    assert!(lisp.eval_str("(total '((\"a\" 1)))").is_err());
+}
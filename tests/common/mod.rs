@@ -0,0 +1,20 @@
+// Helpers shared by the integration tests. Each test file uses only some of them.
+#![allow(dead_code)]
+
+use rlisp::*;
+
+// Evaluates `input` and `expected` in the same session and compares them, so
+// `2` and `2.0` are different results
+pub fn assert_evals_to(lisp: &mut Interpreter, input: &str, expected: &str) {
+    let got = lisp.eval_str(input).unwrap();
+    let expected = lisp.eval_str(expected).unwrap();
+    assert_eq!(got, expected, "{}", input);
+}
+
+// The rendered text of an error that evaluating something should have raised
+pub fn error_text(result: Result<Value, Errors>) -> String {
+    match result {
+        Err(e) => e.to_string(),
+        Ok(v) => panic!("expected an error, got {}", v),
+    }
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use rlisp::*;
+use common::assert_evals_to;
+
+#[test]
+fn only_false_and_nil_are_false() {
+    let mut lisp = Interpreter::new();
+    for value in &["0", "\"\"", "'()", "[]", "{}", "'sym", "0.0"] {
+        assert_evals_to(&mut lisp, &format!("(if {} 'yes 'no)", value), "'yes");
+    }
+    assert_evals_to(&mut lisp, "(if false 'yes 'no)", "'no");
+    assert_evals_to(&mut lisp, "(if nil 'yes 'no)", "'no");
+}
+
+#[test]
+fn and_and_or_stop_early() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define hits (atom 0))").unwrap();
+    lisp.eval_str("(define hit (fn (v) (swap! hits (fn (n) (+ n 1))) v))").unwrap();
+    assert_evals_to(&mut lisp, "(and)", "true");
+    assert_evals_to(&mut lisp, "(or)", "nil");
+    assert_evals_to(&mut lisp, "(and 1 2 3)", "3");
+    assert_evals_to(&mut lisp, "(and 1 nil (hit 2))", "nil");
+    assert_evals_to(&mut lisp, "(or nil false 3 (hit 4))", "3");
+    assert_evals_to(&mut lisp, "(or nil false)", "false");
+    assert_evals_to(&mut lisp, "(deref hits)", "0");
+}
+
+#[test]
+fn cond_takes_the_first_true_clause() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp, "(cond (false 1) (nil 2) ((= 1 1) 3 4) (else 5))", "4");
+    assert_evals_to(&mut lisp, "(cond (false 1) (else 2 3))", "3");
+    assert_evals_to(&mut lisp, "(cond (false 1))", "nil");
+}
+
+#[test]
+fn case_compares_the_key_against_each_datum() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define size (fn (n) (case n ((1 2) 'small) ((3 4) 'medium) (else 'large))))").unwrap();
+    assert_evals_to(&mut lisp, "(size 1)", "'small");
+    assert_evals_to(&mut lisp, "(size 4)", "'medium");
+    assert_evals_to(&mut lisp, "(size 9)", "'large");
+    assert_evals_to(&mut lisp, "(case 'b ((a) 1) ((b c) 2))", "2");
+    assert_evals_to(&mut lisp, "(case 9 ((1) 'a))", "nil");
+}
+
+#[test]
+fn when_and_unless_run_their_body_on_the_test() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp, "(when 0 1 2)", "2");
+    assert_evals_to(&mut lisp, "(when false 1)", "nil");
+    assert_evals_to(&mut lisp, "(unless nil 5)", "5");
+    assert_evals_to(&mut lisp, "(unless 'x 5)", "nil");
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use rlisp::*;
+use std::collections::HashMap;
+use common::{assert_evals_to, error_text};
+
+#[test]
+fn options_are_values_or_nil() {
+    let mut lisp = Interpreter::new();
+    lisp.register_fn("half", |n: i64| if n % 2 == 0 { Some(n / 2) } else { None });
+    lisp.register_fn("or-zero", |n: Option<i64>| n.unwrap_or(0));
+    assert_evals_to(&mut lisp, "(half 8)", "4");
+    assert_evals_to(&mut lisp, "(half 7)", "nil");
+    assert_evals_to(&mut lisp, "(or-zero 5)", "5");
+    assert_evals_to(&mut lisp, "(or-zero nil)", "0");
+    assert!(lisp.eval_str("(or-zero \"5\")").is_err());
+}
+
+#[test]
+fn tuples_are_fixed_length_lists() {
+    let mut lisp = Interpreter::new();
+    lisp.register_fn("div-mod", |a: i64, b: i64| (a / b, a % b));
+    lisp.register_fn("swap", |pair: (String, bool)| (pair.1, pair.0));
+    assert_evals_to(&mut lisp, "(div-mod 7 2)", "'(3 1)");
+    assert_evals_to(&mut lisp, "(swap '(\"a\" true))", "'(true \"a\")");
+    assert!(error_text(lisp.eval_str("(swap '(\"a\"))")).contains("list of length 2"));
+    assert!(lisp.eval_str("(swap '(\"a\" 1))").is_err());
+}
+
+#[test]
+fn native_functions_take_several_converted_arguments() {
+    let mut lisp = Interpreter::new();
+    lisp.register_fn("clamp", |x: f64, lo: f64, hi: f64| x.max(lo).min(hi));
+    lisp.register_fn("repeat", |s: String, n: usize, sep: String| vec![s; n].join(&sep));
+    lisp.register_fn("checked-div", |a: i64, b: i64| -> Result<i64, Errors> {
+        a.checked_div(b).ok_or_else(|| Errors::NativeError(String::from("division by zero")))
+    });
+    assert_evals_to(&mut lisp, "(clamp 5 0 1.5)", "1.5");
+    assert_evals_to(&mut lisp, "(repeat \"ab\" 3 \"-\")", "\"ab-ab-ab\"");
+    assert_evals_to(&mut lisp, "(checked-div 7 2)", "3");
+    assert!(error_text(lisp.eval_str("(checked-div 1 0)")).contains("division by zero"));
+    assert!(error_text(lisp.eval_str("(repeat \"ab\" 3)")).contains("repeat expects 3 arguments, recieved 2 arguments"));
+    assert!(lisp.eval_str("(repeat \"ab\" -1 \"\")").is_err());
+}
+
+// A Rust value that becomes a function in Lisp, so can't be a map key
+#[derive(PartialEq, Eq, Hash)]
+struct Handler;
+
+impl ToLisp for Handler {
+    fn to_lisp(self) -> Result<Value, Errors> {
+        Ok(Value::Function(Builtin::new("handler", Arity::Exactly(0), |_| Ok(Box::new(Value::Nil)))))
+    }
+}
+
+#[test]
+fn maps_with_keys_that_cant_be_keys_are_type_errors() {
+    let mut lisp = Interpreter::new();
+    lisp.register_fn("handlers", || {
+        let mut handlers = HashMap::new();
+        handlers.insert(Handler, 1);
+        handlers
+    });
+    match lisp.eval_str("(handlers)") {
+        Err(e) => assert!(matches!(e.root(), Errors::TypeError(..)), "{}", e),
+        Ok(v) => panic!("handlers returned {}", v),
+    }
+}
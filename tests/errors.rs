@@ -0,0 +1,63 @@
+mod common;
+
+use rlisp::*;
+use common::assert_evals_to;
+
+#[test]
+fn thrown_values_are_caught_as_they_are() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp, "(try (throw 5) (catch e e))", "5");
+    assert_evals_to(&mut lisp, "(try (throw '(a b)) (catch e (first e)))", "'a");
+    assert_evals_to(&mut lisp, "(try 1 2 (catch e 3))", "2");
+    assert_evals_to(&mut lisp, "(try (try (throw 1) (catch e (throw (+ e 1)))) (catch e e))", "2");
+    match lisp.eval_str("(throw 7)") {
+        Err(e) => assert_eq!(e.to_value(), Value::Int(7)),
+        Ok(v) => panic!("throw returned {}", v),
+    }
+}
+
+#[test]
+fn builtin_errors_are_caught_as_error_objects() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp, "(error? (try (car 1) (catch e e)))", "true");
+    assert_evals_to(&mut lisp, "(error-kind (try undefined-name (catch e e)))", "'unbound-symbol");
+    assert_evals_to(&mut lisp, "(error-kind (try (/ 1 0) (catch e e)))", "'arithmetic-error");
+    assert_evals_to(&mut lisp, "(try (+ 1 \"a\") (catch e (list (error-kind e) (error-irritants e))))",
+                    "'(type-error (\"string\" \"number\"))");
+    assert_evals_to(&mut lisp, "(error? 5)", "false");
+}
+
+#[test]
+fn error_makes_error_objects() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define e (error \"boom\" 1 2))").unwrap();
+    assert_evals_to(&mut lisp, "(error? e)", "true");
+    assert_evals_to(&mut lisp, "(error-kind e)", "'error");
+    assert_evals_to(&mut lisp, "(error-message e)", "\"boom\"");
+    assert_evals_to(&mut lisp, "(error-irritants e)", "'(1 2)");
+    assert_evals_to(&mut lisp, "(error-message (try (throw e) (catch caught caught)))", "\"boom\"");
+}
+
+#[test]
+fn finally_runs_last_however_the_body_ends() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define log (atom '()))").unwrap();
+    lisp.eval_str("(define note (fn (x) (swap! log (fn (l) (cons x l)))))").unwrap();
+    assert_evals_to(&mut lisp, "(try (note 'body) 1 (catch e (note 'catch)) (finally (note 'finally) 2))", "1");
+    assert_evals_to(&mut lisp, "(reverse (deref log))", "'(body finally)");
+    lisp.eval_str("(reset! log '())").unwrap();
+    assert_evals_to(&mut lisp, "(try (note 'body) (throw 1) (catch e (note 'catch) e) (finally (note 'finally)))", "1");
+    assert_evals_to(&mut lisp, "(reverse (deref log))", "'(body catch finally)");
+    lisp.eval_str("(reset! log '())").unwrap();
+    assert!(lisp.eval_str("(try (throw 1) (finally (note 'finally)))").is_err());
+    assert!(lisp.eval_str("(try 1 (catch e (throw e)) (finally (note 'again)))").is_ok());
+    assert_evals_to(&mut lisp, "(reverse (deref log))", "'(finally again)");
+}
+
+#[test]
+fn a_malformed_catch_is_reported_before_the_body_runs() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define log (atom '()))").unwrap();
+    assert!(lisp.eval_str("(try (reset! log '(body)) (catch (throw 1)) (finally (reset! log '(finally))))").is_err());
+    assert_evals_to(&mut lisp, "(deref log)", "'()");
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use rlisp::*;
+use common::error_text;
+
+#[test]
+fn embedded_scripts_cannot_exit_the_host() {
+    let mut lisp = Interpreter::new();
+    match lisp.eval_str("(exit 3)") {
+        Err(e) => assert!(matches!(e.root(), Errors::SymbolNotFound(s) if s == "exit")),
+        Ok(v) => panic!("exit returned {}", v),
+    }
+}
+
+#[test]
+fn builtin_arity_errors_name_the_builtin() {
+    let mut lisp = Interpreter::new();
+    lisp.register("pair", Arity::Exactly(2), |p| Ok(Box::new(Value::List(p.into()))));
+    assert!(error_text(lisp.eval_str("(pair 1)")).contains("pair expects 2 arguments, recieved 1 arguments"));
+    assert!(error_text(lisp.eval_str("(abs 1 2)")).contains("abs expects 1 argument, recieved 2 arguments"));
+    assert!(error_text(lisp.eval_str("(-)")).contains("- expects at least 1 argument, recieved 0 arguments"));
+    match lisp.eval_str("(pair)") {
+        Err(e) => assert!(matches!(e.root(), Errors::SignatureError(name, _, 0) if name == "pair")),
+        Ok(v) => panic!("pair returned {}", v),
+    }
+}
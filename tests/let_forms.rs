@@ -0,0 +1,43 @@
+mod common;
+
+use rlisp::*;
+use common::{assert_evals_to, error_text};
+
+#[test]
+fn let_evaluates_values_in_the_enclosing_scope() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define a 10)").unwrap();
+    assert_evals_to(&mut lisp, "(let ((a 1) (b a)) (list a b))", "'(1 10)");
+    assert_evals_to(&mut lisp, "(let (a 1 b a) (list a b))", "'(1 10)");
+    assert_evals_to(&mut lisp, "(let () 1 2)", "2");
+    // The bindings don't outlive the body
+    assert_evals_to(&mut lisp, "(do (let ((a 1)) a) a)", "10");
+}
+
+#[test]
+fn let_star_sees_earlier_bindings() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp, "(let* ((a 1) (b (+ a 1))) (list a b))", "'(1 2)");
+    assert_evals_to(&mut lisp, "(let* (a 1 b (+ a 1) a (* b 10)) (list a b))", "'(20 2)");
+}
+
+#[test]
+fn letrec_values_can_refer_to_each_other() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp,
+                    "(letrec ((ev? (fn (n) (if (= n 0) true (od? (- n 1)))))
+                              (od? (fn (n) (if (= n 0) false (ev? (- n 1))))))
+                       (list (ev? 10) (od? 7)))",
+                    "'(true true)");
+    assert_evals_to(&mut lisp, "(letrec (f (fn (n) (if (= n 0) 1 (* n (f (- n 1)))))) (f 5))", "120");
+}
+
+#[test]
+fn malformed_let_forms_are_reported_by_name() {
+    let mut lisp = Interpreter::new();
+    assert!(error_text(lisp.eval_str("(let (a) 1)")).contains("let expects bindings of the form"));
+    assert!(error_text(lisp.eval_str("(let* ((a 1) b) a)")).contains("let* expects bindings of the form"));
+    assert!(error_text(lisp.eval_str("(letrec ((1 2)) 1)")).contains("letrec expects bindings of the form"));
+    assert!(error_text(lisp.eval_str("(letrec)")).contains("letrec expects a list of bindings"));
+    assert!(error_text(lisp.eval_str("(let* (a 1))")).contains("let* expects a body"));
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use rlisp::*;
+use num_bigint::BigInt;
+use common::assert_evals_to;
+
+#[test]
+fn number_literals_are_read() {
+    assert_eq!(*READ(String::from("-3")).unwrap(), Value::Int(-3));
+    assert_eq!(*READ(String::from("2.5")).unwrap(), Value::Float(2.5));
+    assert_eq!(*READ(String::from("1e10")).unwrap(), Value::Float(1e10));
+    assert_eq!(*READ(String::from("-1.5e-3")).unwrap(), Value::Float(-1.5e-3));
+    assert_eq!(READ(String::from("3/4")).unwrap().to_string(), "3/4");
+    assert_eq!(READ(String::from("-6/8")).unwrap().to_string(), "-3/4");
+    assert_eq!(*READ(String::from("4/2")).unwrap(), Value::Int(2));
+    assert_eq!(READ(String::from("123456789012345678901234567890")).unwrap().to_string(),
+               "123456789012345678901234567890");
+    assert!(READ(String::from("3/-4")).is_err());
+    assert!(READ(String::from("1/0")).is_err());
+}
+
+#[test]
+fn integers_that_overflow_become_bignums() {
+    let mut lisp = Interpreter::new();
+    let max = BigInt::from(i64::MAX);
+    assert_eq!(lisp.eval_str("(+ 9223372036854775807 1)").unwrap(), Value::BigInt(&max + 1));
+    assert_eq!(lisp.eval_str("(* 4611686018427387904 2)").unwrap(), Value::BigInt(&max + 1));
+    assert_eq!(lisp.eval_str("(- -9223372036854775808 1)").unwrap(), Value::BigInt(-max - 2));
+    assert_eq!(lisp.eval_str("(abs -9223372036854775808)").unwrap(), Value::BigInt(BigInt::from(i64::MAX) + 1));
+    // and come back down when they fit again
+    assert_eq!(lisp.eval_str("(- (+ 9223372036854775807 1) 1)").unwrap(), Value::Int(i64::MAX));
+}
+
+#[test]
+fn inexact_numbers_are_contagious() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp, "(+ 1 1/2)", "3/2");
+    assert_evals_to(&mut lisp, "(+ 1/2 0.5)", "1.0");
+    assert_evals_to(&mut lisp, "(* 2 1.5)", "3.0");
+    assert_evals_to(&mut lisp, "(* 1/2 2)", "1");
+    assert_evals_to(&mut lisp, "(exact->inexact 1/4)", "0.25");
+    assert_evals_to(&mut lisp, "(= 1 1.0)", "true");
+    assert_evals_to(&mut lisp, "(= 2/2 1)", "true");
+    assert_evals_to(&mut lisp, "(< 1/3 0.34)", "true");
+}
+
+#[test]
+fn exact_division_gives_rationals() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp, "(/ 6 3)", "2");
+    assert_evals_to(&mut lisp, "(/ 1 3)", "1/3");
+    assert_evals_to(&mut lisp, "(/ 1 3 2)", "1/6");
+    assert_evals_to(&mut lisp, "(/ 1.0 4)", "0.25");
+    assert!(lisp.eval_str("(/ 1 0)").is_err());
+    assert!(lisp.eval_str("(/ 1/2 0)").is_err());
+}
+
+#[test]
+fn floor_round_sqrt_and_expt() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp, "(floor 7/2)", "3");
+    assert_evals_to(&mut lisp, "(floor -7/2)", "-4");
+    assert_evals_to(&mut lisp, "(floor 2.7)", "2.0");
+    assert_evals_to(&mut lisp, "(round 5/2)", "2");
+    assert_evals_to(&mut lisp, "(round 7/2)", "4");
+    assert_evals_to(&mut lisp, "(round 2.5)", "2.0");
+    assert_evals_to(&mut lisp, "(sqrt 16)", "4");
+    assert_evals_to(&mut lisp, "(sqrt 4/9)", "2/3");
+    assert_evals_to(&mut lisp, "(sqrt 2.25)", "1.5");
+    assert_evals_to(&mut lisp, "(sqrt 2)", "1.4142135623730951");
+    assert!(lisp.eval_str("(sqrt -4)").is_err());
+    assert_evals_to(&mut lisp, "(expt 2 10)", "1024");
+    assert_evals_to(&mut lisp, "(expt 2/3 3)", "8/27");
+    assert_evals_to(&mut lisp, "(expt 2 -2)", "1/4");
+    assert_evals_to(&mut lisp, "(expt 4 0.5)", "2.0");
+    assert_eq!(lisp.eval_str("(expt 2 100)").unwrap(), Value::BigInt(BigInt::from(1) << 100));
+    assert!(lisp.eval_str("(expt 0 -1)").is_err());
+}
+
+#[test]
+fn huge_exact_powers_are_refused() {
+    let mut lisp = Interpreter::new();
+    assert!(lisp.eval_str("(expt 2 4000000000)").is_err());
+    assert!(lisp.eval_str("(expt 10 100000000)").is_err());
+    assert!(lisp.eval_str("(expt 2 100000000000000000000)").is_err());
+    // Right at the limit of 2^20 bits
+    assert_evals_to(&mut lisp, "(= (expt 2 1048576) (* 2 (expt 2 1048575)))", "true");
+    assert!(lisp.eval_str("(expt 2 1048577)").is_err());
+    assert!(lisp.eval_str("(expt 1/2 1048577)").is_err());
+    assert!(lisp.eval_str("(expt 3 1048576)").is_err());
+    assert!(lisp.eval_str("(expt 3 661577)").is_ok());
+    assert!(lisp.eval_str("(expt 3 661578)").is_err());
+    // Powers of 0, 1 and -1 stay small
+    assert_evals_to(&mut lisp, "(expt 1 4000000000)", "1");
+    assert_evals_to(&mut lisp, "(expt -1 4000000001)", "-1");
+    assert_evals_to(&mut lisp, "(expt 0 4000000000)", "0");
+}
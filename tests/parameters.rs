@@ -0,0 +1,45 @@
+mod common;
+
+use rlisp::*;
+use common::{assert_evals_to, error_text};
+
+#[test]
+fn optional_parameters_take_their_defaults() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(defn f (a &optional (b 1) c) (list a b c))").unwrap();
+    assert_evals_to(&mut lisp, "(f 5)", "'(5 1 nil)");
+    assert_evals_to(&mut lisp, "(f 5 6)", "'(5 6 nil)");
+    assert_evals_to(&mut lisp, "(f 5 6 7)", "'(5 6 7)");
+    // Defaults are evaluated at each call, and can use earlier parameters
+    assert_evals_to(&mut lisp, "((fn (a &optional (b (* a 2))) b) 4)", "8");
+}
+
+#[test]
+fn rest_parameters_collect_the_remaining_arguments() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(defn f (a & more) (list a more))").unwrap();
+    assert_evals_to(&mut lisp, "(f 1)", "'(1 ())");
+    assert_evals_to(&mut lisp, "(f 1 2 3)", "'(1 (2 3))");
+    assert_evals_to(&mut lisp, "((fn (& all) all))", "'()");
+}
+
+#[test]
+fn keyword_parameters_are_passed_by_name() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(defn f (a &key (b 1) c) (list a b c))").unwrap();
+    assert_evals_to(&mut lisp, "(f 0)", "'(0 1 nil)");
+    assert_evals_to(&mut lisp, "(f 0 :c 3)", "'(0 1 3)");
+    assert_evals_to(&mut lisp, "(f 0 :c 3 :b 2)", "'(0 2 3)");
+    assert!(error_text(lisp.eval_str("(f 0 :z 3)")).contains("f has no keyword parameter :z"));
+    assert!(lisp.eval_str("(f 0 :b)").is_err());
+}
+
+#[test]
+fn arity_errors_name_the_signature() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(defn f (a b) a)").unwrap();
+    lisp.eval_str("(defn g (a &optional b) a)").unwrap();
+    assert!(error_text(lisp.eval_str("(f 1)")).contains("f expects (a b), recieved 1 arguments"));
+    assert!(error_text(lisp.eval_str("(g 1 2 3)")).contains("g expects (a &optional b), recieved 3 arguments"));
+    assert!(error_text(lisp.eval_str("(g)")).contains("g expects (a &optional b), recieved 0 arguments"));
+}
@@ -0,0 +1,34 @@
+use rlisp::*;
+
+fn status(input: &str) -> &'static str {
+    match read_partial(input) {
+        Ok(Partial::Complete(_)) => "complete",
+        Ok(Partial::Incomplete) => "incomplete",
+        Err(_) => "error",
+    }
+}
+
+#[test]
+fn complete_input_is_read() {
+    match read_partial("(+ 1 2) ; done\n3") {
+        Ok(Partial::Complete(forms)) => assert_eq!(forms.len(), 2),
+        _ => panic!("expected two complete forms"),
+    }
+    assert_eq!(status(""), "complete");
+    assert_eq!(status("  ; just a comment"), "complete");
+}
+
+#[test]
+fn input_that_stops_part_way_is_incomplete() {
+    for input in &["(1 2", "(a (b c)\n", "[1 2", "{:a 1", "#{1", "\"abc", "(f \"a\nb",
+                   "#| never closed", "#| #| nested |#", "'", "(a 'b '", "`", ",@", "#_"] {
+        assert_eq!(status(input), "incomplete", "{:?}", input);
+    }
+}
+
+#[test]
+fn input_that_can_never_be_valid_is_an_error() {
+    for input in &[")", "(1 2))", "\"\\q\"", "{:a}", "3/-4 (", "]"] {
+        assert_eq!(status(input), "error", "{:?}", input);
+    }
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use rlisp::*;
+use common::assert_evals_to;
+
+#[test]
+fn unquote_splicing_inserts_list_elements() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define xs '(1 2 3))").unwrap();
+    assert_evals_to(&mut lisp, "`(a ,@xs b)", "'(a 1 2 3 b)");
+    assert_evals_to(&mut lisp, "`(,@xs)", "'(1 2 3)");
+    assert_evals_to(&mut lisp, "`(a ,@'() b)", "'(a b)");
+    assert_evals_to(&mut lisp, "`(,@xs ,@xs)", "'(1 2 3 1 2 3)");
+    assert_evals_to(&mut lisp, "`(a (b ,@xs))", "'(a (b 1 2 3))");
+}
+
+#[test]
+fn unquote_splicing_reads_as_a_form() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp, "'(a ,@b)", "'(a (unquote-splicing b))");
+    assert_evals_to(&mut lisp, "(quasiquote (a (unquote-splicing (list 1 2))))", "'(a 1 2)");
+}
+
+#[test]
+fn unquote_splicing_needs_a_list() {
+    let mut lisp = Interpreter::new();
+    assert!(lisp.eval_str("`(a ,@1)").is_err());
+    assert!(lisp.eval_str("`,@(list 1)").is_err());
+}
+
+#[test]
+fn macros_splice_their_bodies() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(defmacro my-when (fn (test & body) `(if ,test (do ,@body) nil)))").unwrap();
+    assert_evals_to(&mut lisp, "(my-when true 1 2 3)", "3");
+    assert_evals_to(&mut lisp, "(my-when false 1 2 3)", "nil");
+    assert_evals_to(&mut lisp, "(macroexpand (my-when x (prn 1) 2))", "'(if x (do (prn 1) 2) nil)");
+
+    lisp.eval_str("(defmacro call-with (fn (f & args) `(,f ,@args)))").unwrap();
+    assert_evals_to(&mut lisp, "(call-with + 1 2 3)", "6");
+}
+
+#[test]
+fn macroexpand_1_expands_one_level_only() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(defmacro my-unless (fn (test & body) `(if ,test nil (do ,@body))))").unwrap();
+    lisp.eval_str("(defmacro my-if-not-not (fn (test x) `(my-unless (not ,test) ,x)))").unwrap();
+    assert_evals_to(&mut lisp, "(macroexpand-1 (my-if-not-not a 1))", "'(my-unless (not a) 1)");
+    assert_evals_to(&mut lisp, "(macroexpand (my-if-not-not a 1))", "'(if (not a) nil (do 1))");
+    // Forms that aren't macro calls are left as they are
+    assert_evals_to(&mut lisp, "(macroexpand-1 (+ 1 2))", "'(+ 1 2)");
+    assert_evals_to(&mut lisp, "(my-if-not-not true 5)", "5");
+}
+
+#[test]
+fn nested_quasiquotes_only_evaluate_the_innermost_level() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define xs '(1 2))").unwrap();
+    assert_evals_to(&mut lisp, "`(1 `(2 ,(3 ,(+ 1 3))))", "'(1 (quasiquote (2 (unquote (3 4)))))");
+    assert_evals_to(&mut lisp, "`(1 `(2 ,(3 ,@xs)))", "'(1 (quasiquote (2 (unquote (3 1 2)))))");
+    assert_evals_to(&mut lisp, "`(1 `(2 ,@(3 ,@xs)))", "'(1 (quasiquote (2 (unquote-splicing (3 1 2)))))");
+    assert_evals_to(&mut lisp, "`(1 `(2 ,(+ 1 2)))", "'(1 (quasiquote (2 (unquote (+ 1 2)))))");
+}
+
+#[test]
+fn macros_can_write_macros() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(defmacro def-constant-macro (fn (name value) `(defmacro ,name (fn () `(quote ,',value)))))").unwrap();
+    lisp.eval_str("(def-constant-macro three 3)").unwrap();
+    assert_evals_to(&mut lisp, "(three)", "3");
+}
+
+#[test]
+fn quasiquote_walks_into_collections() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define x 1)").unwrap();
+    lisp.eval_str("(define xs '(2 3))").unwrap();
+    assert_evals_to(&mut lisp, "`[,x]", "[1]");
+    assert_evals_to(&mut lisp, "`[a ,x ,@xs (b ,x)]", "['a 1 2 3 '(b 1)]");
+    assert_evals_to(&mut lisp, "`{:a ,x ,x :b}", "{:a 1 1 :b}");
+    assert_evals_to(&mut lisp, "`#{,x ,@xs}", "#{1 2 3}");
+    assert_evals_to(&mut lisp, "`(a [,x {:k [,@xs]}])", "(list 'a [1 {:k [2 3]}])");
+    assert_evals_to(&mut lisp, "``[,,x]", "'(quasiquote [(unquote 1)])");
+}
@@ -0,0 +1,55 @@
+use rlisp::*;
+
+fn read(input: &str) -> Value {
+    *READ(String::from(input)).unwrap()
+}
+
+#[test]
+fn read_takes_exactly_one_form() {
+    let mut lisp = Interpreter::new();
+    assert_eq!(lisp.eval_str("(read \" (+ 1 2) ; sum\")").unwrap(), lisp.eval_str("'(+ 1 2)").unwrap());
+    assert!(lisp.eval_str("(read \"1 2\")").is_err());
+    assert!(lisp.eval_str("(read \"\")").is_err());
+    assert!(READ(String::from("a b")).is_err());
+}
+
+#[test]
+fn comments_are_skipped() {
+    assert_eq!(read("(1 ; to the end of the line\n 2) ; and after"), read("(1 2)"));
+    assert_eq!(read("(1 #| a block |# 2)"), read("(1 2)"));
+    assert_eq!(read("(1 #| outer #| inner |# still outer |# 2)"), read("(1 2)"));
+    assert_eq!(read("#| before |# 3"), Value::Int(3));
+    assert!(READ(String::from("(1 #| never closed 2)")).is_err());
+    assert!(READ(String::from("#| #| |# 1")).is_err());
+}
+
+#[test]
+fn datum_comments_skip_the_next_form() {
+    assert_eq!(read("(1 #_ 2 3)"), read("(1 3)"));
+    assert_eq!(read("(1 #_ (a (b c)) 4)"), read("(1 4)"));
+    assert_eq!(read("(1 #_ #_ 2 3 4)"), read("(1 4)"));
+    assert_eq!(read("[1 #_ 2]"), read("[1]"));
+    assert!(READ(String::from("(1 #_)")).is_err());
+}
+
+#[test]
+fn string_escapes() {
+    assert_eq!(read(r#""a\nb\tc\r\0""#), Value::Str(String::from("a\nb\tc\r\0")));
+    assert_eq!(read(r#""\\ \"quoted\"""#), Value::Str(String::from("\\ \"quoted\"")));
+    assert_eq!(read(r#""\u{48}\u{e9}\u{1F600}""#), Value::Str(String::from("Hé😀")));
+    assert!(READ(String::from(r#""\q""#)).is_err());
+    assert!(READ(String::from(r#""\u{110000}""#)).is_err());
+    assert!(READ(String::from(r#""\u{}""#)).is_err());
+    assert!(READ(String::from(r#""\u48""#)).is_err());
+}
+
+#[test]
+fn printed_data_reads_back_the_same() {
+    for input in &[r#""line\nbreak \"quoted\" back\\slash tab\t""#, "-3", "2.5", "3/4",
+                   "123456789012345678901234567890", "sym", ":key",
+                   "(1 (2 \"x\") () [3 {:a #{1 2}}])", "{\"k\" [1.5 -2/3]}"] {
+        let value = read(input);
+        let printed = PRINT(&value);
+        assert_eq!(read(&printed), value, "{} printed as {}", input, printed);
+    }
+}
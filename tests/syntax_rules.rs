@@ -0,0 +1,77 @@
+mod common;
+
+use rlisp::*;
+use common::assert_evals_to;
+
+#[test]
+fn gensyms_are_fresh() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp, "(= (gensym) (gensym))", "false");
+    lisp.eval_str("(define g (gensym \"tmp\"))").unwrap();
+    assert_evals_to(&mut lisp, "(= g g)", "true");
+    // A gensym can't be read back, so no symbol in source code is equal to one
+    assert!(lisp.eval_str("(read (str g))").is_err());
+}
+
+#[test]
+fn ellipses_match_and_repeat_any_number_of_forms() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define-syntax my-list (syntax-rules () ((_ x ...) (list x ...))))").unwrap();
+    assert_evals_to(&mut lisp, "(my-list)", "'()");
+    assert_evals_to(&mut lisp, "(my-list 1 (+ 1 1) 3)", "'(1 2 3)");
+    lisp.eval_str("(define-syntax first-and-rest (syntax-rules () ((_ a b ... z) (list a '(b ...) z))))").unwrap();
+    assert_evals_to(&mut lisp, "(first-and-rest 1 2 3 4)", "'(1 (2 3) 4)");
+    assert_evals_to(&mut lisp, "(first-and-rest 1 4)", "'(1 () 4)");
+    assert!(lisp.eval_str("(first-and-rest 1)").is_err());
+}
+
+#[test]
+fn nested_ellipses_repeat_for_each_group() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define-syntax sums (syntax-rules () ((_ (x ...) ...) (list (+ x ...) ...))))").unwrap();
+    assert_evals_to(&mut lisp, "(sums (1 2) () (3 4 5))", "'(3 0 12)");
+    lisp.eval_str("(define-syntax my-let* (syntax-rules ()
+                     ((_ () body ...) (let () body ...))
+                     ((_ ((n v) rest ...) body ...) (let ((n v)) (my-let* (rest ...) body ...)))))").unwrap();
+    assert_evals_to(&mut lisp, "(my-let* ((a 1) (b (+ a 1))) (* a b))", "2");
+}
+
+#[test]
+fn literals_match_only_themselves() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define-syntax arrow (syntax-rules (=>) ((_ a => b) (list a b)) ((_ a b c) 'no-arrow)))").unwrap();
+    assert_evals_to(&mut lisp, "(arrow 1 => 2)", "'(1 2)");
+    assert_evals_to(&mut lisp, "(arrow 1 -> 2)", "'no-arrow");
+    assert!(lisp.eval_str("(arrow 1 2)").is_err());
+}
+
+#[test]
+fn names_bound_by_a_template_are_renamed() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define-syntax my-or (syntax-rules ()
+                     ((_) false)
+                     ((_ e rest ...) (let ((t e)) (if t t (my-or rest ...))))))").unwrap();
+    // The template's `t` doesn't capture the caller's
+    assert_evals_to(&mut lisp, "(let ((t 5)) (my-or false t))", "5");
+    lisp.eval_str("(define-syntax swap! (syntax-rules () ((_ a b) (let ((tmp a)) (set! a b) (set! b tmp)))))").unwrap();
+    assert_evals_to(&mut lisp, "(let ((tmp 1) (other 2)) (swap! tmp other) (list tmp other))", "'(2 1)");
+    lisp.eval_str("(define-syntax twice (syntax-rules () ((_ e) ((fn (x) (+ x x)) e))))").unwrap();
+    assert_evals_to(&mut lisp, "(let ((x 4)) (twice (* x 10)))", "80");
+}
+
+#[test]
+fn keyword_parameters_of_template_functions_keep_their_names() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define-syntax defscaled (syntax-rules () ((_ name) (define name (fn (a &key (k 2)) (* a k))))))").unwrap();
+    lisp.eval_str("(defscaled scale)").unwrap();
+    assert_evals_to(&mut lisp, "(scale 3)", "6");
+    assert_evals_to(&mut lisp, "(scale 3 :k 10)", "30");
+}
+
+#[test]
+fn free_names_in_a_template_are_looked_up_at_the_use_site() {
+    let mut lisp = Interpreter::new();
+    assert_evals_to(&mut lisp,
+                    "(let ((a 1)) (define-syntax m (syntax-rules () ((_ x) (list x a)))) (let ((a 2)) (m 0)))",
+                    "'(0 2)");
+}
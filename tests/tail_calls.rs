@@ -0,0 +1,24 @@
+use rlisp::*;
+
+#[test]
+fn self_recursive_loop_runs_a_million_times() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define loop (fn (n) (if (= n 1000000) n (loop (+ n 1)))))").unwrap();
+    assert_eq!(lisp.eval_str("(loop 0)").unwrap(), Value::Int(1000000));
+}
+
+#[test]
+fn tail_position_in_do_and_let() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define loop (fn (n) (do (list n) (let (m (+ n 1)) (if (= m 100000) m (loop m))))))").unwrap();
+    assert_eq!(lisp.eval_str("(loop 0)").unwrap(), Value::Int(100000));
+}
+
+#[test]
+fn stdlib_folds_over_long_lists() {
+    let mut lisp = Interpreter::new();
+    lisp.eval_str("(define build (fn (i n acc) (if (= i n) acc (build (+ i 1) n (cons i acc)))))").unwrap();
+    lisp.eval_str("(define xs (build 0 3000 '()))").unwrap();
+    assert_eq!(lisp.eval_str("(count (map (fn (x) (+ x 1)) xs))").unwrap(), Value::Int(3000));
+    assert_eq!(lisp.eval_str("(foldr + 0 xs)").unwrap(), Value::Int(4498500));
+}
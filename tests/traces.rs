@@ -0,0 +1,45 @@
+mod common;
+
+use rlisp::*;
+use common::error_text;
+
+#[test]
+fn errors_show_where_they_happened_and_the_calls_that_led_there() {
+    let program = "\
+(defn inner (x)
+  (+ x \"oops\"))
+
+(defn middle (x)
+  (list (inner x)))
+
+(defn outer (x)
+  (first (middle x)))
+
+(outer 1)
+";
+    assert_eq!(error_text(Interpreter::new().eval_program(program, "prog.lisp")), "\
+Type Error: Expected number, recieved string
+  in (+ x \"oops\")
+  at prog.lisp:2:3
+Backtrace:
+  inner called at prog.lisp:5:9
+  middle called at prog.lisp:8:10
+  outer called at prog.lisp:10:1
+  while loading form 4 of prog.lisp");
+}
+
+#[test]
+fn long_backtraces_are_cut_short() {
+    let program = "\
+(defn down (n)
+  (if (= n 0)
+      (car 1)
+      (+ 1 (down (- n 1)))))
+(down 30)
+";
+    let text = error_text(Interpreter::new().eval_program(program, "deep.lisp"));
+    let frames = text.lines().filter(|l| l.starts_with("  down called at deep.lisp:4:12")).count();
+    assert_eq!(frames, 20, "{}", text);
+    assert!(text.starts_with("Symbol car is undefined\n  in (car 1)\n  at deep.lisp:3:7\nBacktrace:\n"), "{}", text);
+    assert!(text.ends_with("\n  ... 11 more\n  while loading form 2 of deep.lisp"), "{}", text);
+}